@@ -0,0 +1,164 @@
+use std::time::{Duration, Instant};
+
+use bevy::{prelude::*, app::AppExit};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{define::*, common::BulletPool};
+
+pub const BENCH_TICKS: u32 = 600;
+const BENCH_ENEMIES: usize = 100;
+const BENCH_PLAYER_SHOOTS: usize = 500;
+
+// keeps the playfield full of bullets and reports how long gameplay takes per fixed tick, run with --bench <bullets>
+pub struct BenchPlugin {
+    pub bullets: usize,
+}
+
+impl Plugin for BenchPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(BenchStatus {
+                bullets: self.bullets,
+                rng: StdRng::seed_from_u64(0),
+                tick_start: None,
+                ticks: 0,
+                total: Duration::ZERO,
+                worst: Duration::ZERO,
+            })
+            .add_systems(FixedUpdate, (
+                (bench_spawn_system, bench_tick_start_system).chain().before(GameSet::Timer),
+                bench_tick_end_system.after(GameSet::Item),
+            ).run_if(in_state(GameState::InGame)));
+    }
+}
+
+#[derive(Resource)]
+struct BenchStatus {
+    bullets: usize,
+    rng: StdRng,
+    tick_start: Option<Instant>,
+    ticks: u32,
+    total: Duration,
+    worst: Duration,
+}
+
+impl BenchStatus {
+    fn average(&self) -> Duration {
+        self.total / self.ticks.max(1)
+    }
+}
+
+// bullets cross the upper half sideways, out of reach of the player so the run does not end early
+fn bench_spawn_system(
+    mut commands: Commands,
+    mut bench_status: ResMut<BenchStatus>,
+    mut bullet_pool: ResMut<BulletPool>,
+    window_size_limit: Res<WindowSizeLimit>,
+    enemy_shoots: Query<(), With<FromEnemyShoot>>,
+    enemies: Query<(), With<Enemy>>,
+    player_shoots: Query<(), With<FromPlayerShoot>>,
+) {
+    let (left, right, top) = (window_size_limit.left, window_size_limit.right, window_size_limit.top);
+    let bullets = bench_status.bullets;
+    let rng = &mut bench_status.rng;
+
+    for _ in enemy_shoots.iter().count()..bullets {
+        let velocity = if rng.gen_bool(0.5) { SHOOT_VELOCITY } else { -SHOOT_VELOCITY };
+        let translation = Vec3::new(rng.gen_range(left..right), rng.gen_range(0.0..top), 0.0);
+        bullet_pool.spawn(&mut commands, translation, (
+            Velocity { x: velocity, y: 0.0 },
+            Hitbox::circle(SHOOT_RADIUS),
+            FromEnemyShoot,
+        ));
+    }
+
+    for _ in enemies.iter().count()..BENCH_ENEMIES {
+        commands.spawn((
+            SpatialBundle::from_transform(Transform::from_xyz(rng.gen_range(left..right), rng.gen_range(0.0..top), 9.0)),
+            Enemy {
+                shoot_interval: Timer::from_seconds(3600.0, TimerMode::Repeating),
+                point: 0.0,
+                drop_chance: 0.0,
+            },
+            Health::new(SHOOT_DAMAGE_NORMAL),
+            Hitbox::circle(ENEMY_RADIUS),
+            AutoDespawn,
+            Velocity { x: 0.0, y: 0.0 },
+            OnGameScreen,
+        ));
+    }
+
+    for _ in player_shoots.iter().count()..BENCH_PLAYER_SHOOTS {
+        let translation = Vec3::new(rng.gen_range(left..right), rng.gen_range(window_size_limit.bottom..0.0), 0.0);
+        bullet_pool.spawn(&mut commands, translation, (
+            Velocity { x: 0.0, y: SHOOT_VELOCITY },
+            Damage { value: SHOOT_DAMAGE_NORMAL },
+            Hitbox::player_shoot(),
+            FromPlayerShoot,
+        ));
+    }
+}
+
+fn bench_tick_start_system(
+    mut bench_status: ResMut<BenchStatus>,
+) {
+    bench_status.tick_start = Some(Instant::now());
+}
+
+fn bench_tick_end_system(
+    mut bench_status: ResMut<BenchStatus>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let Some(tick_start) = bench_status.tick_start.take() else {
+        return;
+    };
+    let elapsed = tick_start.elapsed();
+
+    bench_status.ticks += 1;
+    bench_status.total += elapsed;
+    bench_status.worst = bench_status.worst.max(elapsed);
+
+    if bench_status.ticks == BENCH_TICKS {
+        let budget = Duration::from_secs_f64(1.0 / FIXED_UPDATE_HZ);
+        let average = bench_status.average();
+        println!(
+            "bench: {} bullets topped up every tick, {} ticks, average {:.3} ms, worst {:.3} ms, budget {:.3} ms ({})",
+            bench_status.bullets,
+            bench_status.ticks,
+            average.as_secs_f64() * 1000.0,
+            bench_status.worst.as_secs_f64() * 1000.0,
+            budget.as_secs_f64() * 1000.0,
+            if average < budget { "ok" } else { "over budget" },
+        );
+        exit.send(AppExit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{headless_app, replay::ReplayMode, rng::GameSeed};
+
+    const BENCH_BULLETS: usize = 5000;
+
+    // timing only means something in an optimized build: cargo test --release -- --ignored bench
+    #[test]
+    #[ignore = "timing, run with --release"]
+    fn bench_fits_the_tick_budget() {
+        let mut app = headless_app(Vec2::new(WINDOW_SIZE_WIDTH, WINDOW_SIZE_HEIGHT), GameSeed(0), ReplayMode::Off);
+        app.add_plugins(BenchPlugin { bullets: BENCH_BULLETS });
+        app.finish();
+        app.cleanup();
+        for _ in 0..BENCH_TICKS * 2 {
+            app.update();
+            if app.world.resource::<BenchStatus>().ticks == BENCH_TICKS {
+                break;
+            }
+        }
+
+        let bench_status = app.world.resource::<BenchStatus>();
+        assert_eq!(bench_status.ticks, BENCH_TICKS);
+        let budget = Duration::from_secs_f64(1.0 / FIXED_UPDATE_HZ);
+        assert!(bench_status.average() < budget, "average {:?} over the {:?} budget", bench_status.average(), budget);
+    }
+}
@@ -0,0 +1,216 @@
+use bevy::prelude::*;
+
+use crate::{define::*, common::BulletPool, enemy::{boss::Boss, EnemyDefeatedEvent}, particle::{ParticleEvent, ParticleEmitter}, input::{GameInput, GameButton}};
+
+pub struct BombPlugin;
+
+impl Plugin for BombPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, (
+            bomb_input_system,
+            bomb_system,
+            player_invulnerable_system,
+        ).chain().in_set(GameSet::Bomb).run_if(in_state(GameState::InGame)));
+    }
+}
+
+fn bomb_input_system(
+    game_input: Res<GameInput>,
+    player: Query<&Player>,
+    mut player_status: ResMut<PlayerStatus>,
+) {
+    if !game_input.tick_just_pressed(GameButton::Bomb) {
+        return;
+    }
+    if player.get_single().is_ok_and(|player| player.is_enable) {
+        player_status.request_bomb();
+    }
+}
+
+// clears every enemy shot and hits every enemy on screen
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn bomb_system(
+    mut commands: Commands,
+    mut bullet_pool: ResMut<BulletPool>,
+    player: Query<(Entity, &Transform), With<Player>>,
+    enemy_shoots: Query<(Entity, &Transform), With<FromEnemyShoot>>,
+    mut enemies: Query<(Entity, &Transform, &Enemy, &mut Health, Has<Invulnerable>, Has<Boss>)>,
+    mut player_status: ResMut<PlayerStatus>,
+    mut defeated_events: EventWriter<EnemyDefeatedEvent>,
+    mut particle_events: EventWriter<ParticleEvent>,
+) {
+    if !player_status.is_bomb {
+        return;
+    }
+    player_status.is_bomb = false;
+
+    // missed before the bomb went off
+    let Ok((player_entity, player_transform)) = player.get_single() else {
+        return;
+    };
+    player_status.bombs -= 1;
+
+    for (enemy_shoot, transform) in enemy_shoots.iter() {
+        bullet_pool.release(&mut commands, enemy_shoot);
+        particle_events.send(ParticleEvent { position: transform.translation.truncate(), emitter: ParticleEmitter::BULLET_CANCEL });
+    }
+
+    for (entity, transform, enemy, mut health, is_invulnerable, is_boss) in enemies.iter_mut() {
+        if is_invulnerable {
+            continue;
+        }
+
+        health.current -= BOMB_DAMAGE;
+        // a broken boss is left to its phase system
+        if health.current > 0.0 || is_boss {
+            commands.entity(entity).insert(HitFlash::new());
+            continue;
+        }
+
+        commands.entity(entity).despawn();
+        let point = player_status.kill(enemy.point);
+
+        let x = transform.translation.x;
+        let y = transform.translation.y;
+        particle_events.send(ParticleEvent { position: Vec2::new(x, y), emitter: ParticleEmitter::ENEMY_DEFEATED });
+        commands.spawn((ShowScorePoint {x, y, point}, OnGameScreen));
+        defeated_events.send(EnemyDefeatedEvent { x, y, drop_chance: enemy.drop_chance });
+    }
+
+    commands.entity(player_entity).insert(Invulnerable::new(BOMB_INVULNERABLE_SECONDS));
+    commands.spawn((
+        ShowBombPoint {
+            x: player_transform.translation.x,
+            y: player_transform.translation.y,
+        },
+        OnGameScreen,
+    ));
+}
+
+fn player_invulnerable_system(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Invulnerable), With<Player>>,
+    time: Res<Time>,
+) {
+    for (entity, mut invulnerable) in query.iter_mut() {
+        if invulnerable.timer.tick(time.delta()).just_finished() {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{headless_app, enemy::EnemySchedule, replay::ReplayMode, rng::GameSeed};
+
+    // an empty stage with the player in place and ready
+    fn bomb_app() -> App {
+        let mut app = headless_app(Vec2::new(WINDOW_SIZE_WIDTH, WINDOW_SIZE_HEIGHT), GameSeed(1), ReplayMode::Off);
+        app.finish();
+        app.cleanup();
+        while *app.world.resource::<State<GameState>>().get() != GameState::InGame {
+            app.update();
+        }
+        app.world.resource_mut::<EnemySchedule>().cursor = usize::MAX;
+        while !app.world.query::<&Player>().iter(&app.world).any(|player| player.is_enable) {
+            app.update();
+        }
+        app
+    }
+
+    fn player(app: &mut App) -> (Entity, Vec2) {
+        let (entity, transform) = app.world.query_filtered::<(Entity, &Transform), With<Player>>().single(&app.world);
+        (entity, transform.translation.truncate())
+    }
+
+    fn spawn_shot(app: &mut App, position: Vec2) -> Entity {
+        app.world.spawn((
+            SpatialBundle::from_transform(Transform::from_translation(position.extend(0.0))),
+            Velocity { x: 0.0, y: 0.0 },
+            Hitbox::circle(SHOOT_RADIUS),
+            FromEnemyShoot,
+        )).id()
+    }
+
+    fn enemy_shots(app: &mut App) -> usize {
+        app.world.query_filtered::<(), With<FromEnemyShoot>>().iter(&app.world).count()
+    }
+
+    fn press_bomb(app: &mut App) {
+        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::X);
+        for _ in 0..3 {
+            app.update();
+        }
+        app.world.resource_mut::<Input<KeyCode>>().release(KeyCode::X);
+        app.update();
+    }
+
+    fn wait_out_invulnerability(app: &mut App) {
+        let (entity, _) = player(app);
+        while app.world.entity(entity).contains::<Invulnerable>() {
+            app.update();
+        }
+    }
+
+    #[test]
+    fn no_bomb_without_stock() {
+        let mut player_status = PlayerStatus { bombs: 0, ..PlayerStatus::default() };
+        assert!(!player_status.request_bomb());
+        assert!(!player_status.is_bomb);
+
+        // one at a time
+        player_status.bombs = 1;
+        assert!(player_status.request_bomb());
+        assert!(!player_status.request_bomb());
+    }
+
+    #[test]
+    fn bombs_clear_shots_until_the_stock_runs_out() {
+        let mut app = bomb_app();
+        for bombs in (0..PLAYER_BOMBS).rev() {
+            spawn_shot(&mut app, Vec2::new(0.0, 200.0));
+            press_bomb(&mut app);
+            assert_eq!(app.world.resource::<PlayerStatus>().bombs, bombs);
+            assert_eq!(enemy_shots(&mut app), 0);
+            let (entity, _) = player(&mut app);
+            assert!(app.world.entity(entity).contains::<Invulnerable>());
+            wait_out_invulnerability(&mut app);
+        }
+
+        // out of stock, the button does nothing
+        spawn_shot(&mut app, Vec2::new(0.0, 200.0));
+        press_bomb(&mut app);
+        assert_eq!(app.world.resource::<PlayerStatus>().bombs, 0);
+        assert_eq!(enemy_shots(&mut app), 1);
+        let (entity, _) = player(&mut app);
+        assert!(!app.world.entity(entity).contains::<Invulnerable>());
+    }
+
+    #[test]
+    fn auto_bomb_takes_the_hit() {
+        let mut app = bomb_app();
+        let (entity, position) = player(&mut app);
+        spawn_shot(&mut app, position);
+        // the hit asks for the bomb, it goes off in the next tick
+        for _ in 0..3 {
+            app.update();
+        }
+
+        let player_status = app.world.resource::<PlayerStatus>();
+        assert_eq!(player_status.lives, PLAYER_LIVES);
+        assert_eq!(player_status.bombs, PLAYER_BOMBS - 1);
+        assert!(app.world.entity(entity).contains::<Invulnerable>());
+        assert_eq!(enemy_shots(&mut app), 0);
+
+        // with no bomb left the hit costs a life
+        wait_out_invulnerability(&mut app);
+        app.world.resource_mut::<PlayerStatus>().bombs = 0;
+        let (_, position) = player(&mut app);
+        spawn_shot(&mut app, position);
+        for _ in 0..3 {
+            app.update();
+        }
+        assert_eq!(app.world.resource::<PlayerStatus>().lives, PLAYER_LIVES - 1);
+    }
+}
@@ -1,114 +1,591 @@
-use bevy::{prelude::*, sprite::collide_aabb::collide};
+use bevy::{prelude::*, utils::HashSet};
 
-use crate::define::*;
+use crate::{define::*, common::BulletPool, enemy::{boss::Boss, EnemyDefeatedEvent}, particle::{ParticleEvent, ParticleEmitter}, sound::SoundEvent};
 
 pub struct CollisionPlugin;
 
 impl Plugin for CollisionPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (
-            player_shoot_collision_system,
-            enemy_shoot_collision_system,
-            player_enemy_collision_system,
-        ));
+        app
+            .add_systems(Startup, collision_grid_setup_system)
+            .add_systems(FixedUpdate, (
+                collision_grid_system,
+                player_shoot_collision_system,
+                enemy_shoot_collision_system,
+                graze_system,
+                player_enemy_collision_system,
+            ).chain().in_set(GameSet::Collision).run_if(in_state(GameState::InGame)));
     }
 }
 
+// uniform grid over the playfield and its despawn margin, entities are filed under every cell their bounds touch
+pub struct SpatialGrid {
+    origin: Vec2,
+    columns: usize,
+    rows: usize,
+    cells: Vec<Vec<Entity>>,
+}
+
+impl SpatialGrid {
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        let size = ((max - min) / COLLISION_CELL_SIZE).ceil();
+        let (columns, rows) = (size.x.max(1.0) as usize, size.y.max(1.0) as usize);
+        Self { origin: min, columns, rows, cells: vec![Vec::new(); columns * rows] }
+    }
+
+    // anything outside the grid lands in the edge cells
+    fn cell(&self, point: Vec2) -> (usize, usize) {
+        let cell = ((point - self.origin) / COLLISION_CELL_SIZE).floor();
+        (
+            cell.x.clamp(0.0, (self.columns - 1) as f32) as usize,
+            cell.y.clamp(0.0, (self.rows - 1) as f32) as usize,
+        )
+    }
+
+    // keeps the cell allocations for the next tick
+    pub fn clear(&mut self) {
+        for cell in self.cells.iter_mut() {
+            cell.clear();
+        }
+    }
+
+    pub fn insert(&mut self, entity: Entity, (min, max): (Vec2, Vec2)) {
+        let ((x0, y0), (x1, y1)) = (self.cell(min), self.cell(max));
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                self.cells[y * self.columns + x].push(entity);
+            }
+        }
+    }
+
+    // candidates whose bounds share a cell with the given bounds, sorted so results never depend on insert order
+    pub fn query(&self, (min, max): (Vec2, Vec2), found: &mut Vec<Entity>) {
+        found.clear();
+        let ((x0, y0), (x1, y1)) = (self.cell(min), self.cell(max));
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                found.extend_from_slice(&self.cells[y * self.columns + x]);
+            }
+        }
+        found.sort_unstable();
+        found.dedup();
+    }
+}
+
+#[derive(Resource)]
+pub struct CollisionGrid {
+    enemies: SpatialGrid,
+    enemy_shoots: SpatialGrid,
+}
+
+fn collision_grid_setup_system(
+    mut commands: Commands,
+    window_size_limit: Res<WindowSizeLimit>,
+) {
+    let margin = Vec2::splat(WINDOW_SIZE_MARGIN);
+    let min = Vec2::new(window_size_limit.left, window_size_limit.bottom) - margin;
+    let max = Vec2::new(window_size_limit.right, window_size_limit.top) + margin;
+
+    commands.insert_resource(CollisionGrid {
+        enemies: SpatialGrid::new(min, max),
+        enemy_shoots: SpatialGrid::new(min, max),
+    });
+}
+
+fn collision_grid_system(
+    mut collision_grid: ResMut<CollisionGrid>,
+    enemies: Query<(Entity, &Transform, &Hitbox), With<Enemy>>,
+    enemy_shoots: Query<(Entity, &Transform, &Hitbox), With<FromEnemyShoot>>,
+) {
+    collision_grid.enemies.clear();
+    for (entity, transform, hitbox) in enemies.iter() {
+        collision_grid.enemies.insert(entity, hitbox_bounds(hitbox, transform.translation.truncate()));
+    }
+
+    collision_grid.enemy_shoots.clear();
+    for (entity, transform, hitbox) in enemy_shoots.iter() {
+        collision_grid.enemy_shoots.insert(entity, hitbox_bounds(hitbox, transform.translation.truncate()));
+    }
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
 fn player_shoot_collision_system(
     mut commands: Commands,
-    player_shoots: Query<(Entity, &Transform), With<FromPlayerShoot>>,
-    enemies: Query<(Entity, &Transform, &Enemy), With<Enemy>>,
+    mut bullet_pool: ResMut<BulletPool>,
+    collision_grid: Res<CollisionGrid>,
+    player_shoots: Query<(Entity, &Transform, &Hitbox, &Damage), With<FromPlayerShoot>>,
+    mut enemies: Query<(&Transform, &Hitbox, &Enemy, &mut Health, Has<Invulnerable>, Has<Boss>)>,
     mut player_status: ResMut<PlayerStatus>,
+    mut defeated_events: EventWriter<EnemyDefeatedEvent>,
+    mut particle_events: EventWriter<ParticleEvent>,
+    mut candidates: Local<Vec<Entity>>,
 ) {
-    for (enemy_entity, enemy_transform, enemy) in enemies.iter() {
-        for (player_shoot_entity, player_shoot_transform) in player_shoots.iter() {
-            let is_collide = collide(
-                enemy_transform.translation,
-                Vec2::new(ENEMY_RADIUS, ENEMY_RADIUS),
-                player_shoot_transform.translation,
-                Vec2::new(PLAYER_RADIUS, PLAYER_RADIUS));
+    // an enemy goes down once even when several shots reach it in the same tick
+    let mut defeated_enemies = HashSet::new();
+
+    for (player_shoot_entity, player_shoot_transform, player_shoot_hitbox, damage) in player_shoots.iter() {
+        collision_grid.enemies.query(hitbox_bounds(player_shoot_hitbox, player_shoot_transform.translation.truncate()), &mut candidates);
+
+        for enemy_entity in candidates.iter() {
+            let Ok((enemy_transform, enemy_hitbox, enemy, mut health, is_invulnerable, is_boss)) = enemies.get_mut(*enemy_entity) else {
+                continue;
+            };
 
             // hit a player shoot to enemy
-            if is_collide != None {
-                commands.entity(enemy_entity).despawn();
-                commands.entity(player_shoot_entity).despawn();
+            if !defeated_enemies.contains(enemy_entity) && is_collide(enemy_transform, enemy_hitbox, player_shoot_transform, player_shoot_hitbox) {
+                bullet_pool.release(&mut commands, player_shoot_entity);
+                particle_events.send(ParticleEvent { position: player_shoot_transform.translation.truncate(), emitter: ParticleEmitter::HIT_SPARK });
+                if is_invulnerable {
+                    break;
+                }
+                health.current -= damage.value;
+
+                if health.current > 0.0 {
+                    commands.entity(*enemy_entity).insert(HitFlash::new());
+                    break;
+                }
+
+                // the boss moves on to its next phase instead
+                if is_boss {
+                    break;
+                }
+
+                defeated_enemies.insert(*enemy_entity);
+                commands.entity(*enemy_entity).despawn();
 
                 let x = enemy_transform.translation.x;
                 let y = enemy_transform.translation.y;
+                let point = player_status.kill(enemy.point);
 
-                player_status.score += enemy.point;
-
-                commands.spawn(ShowBangPoint {x, y});
+                particle_events.send(ParticleEvent { position: Vec2::new(x, y), emitter: ParticleEmitter::ENEMY_DEFEATED });
+                commands.spawn((ShowScorePoint {x, y, point}, OnGameScreen));
+                defeated_events.send(EnemyDefeatedEvent { x, y, drop_chance: enemy.drop_chance });
+                break;
             }
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn enemy_shoot_collision_system(
     mut commands: Commands,
-    enemy_shoots: Query<(Entity, &Transform), With<FromEnemyShoot>>,
-    player: Query<(Entity, &Transform, &Player), With<Player>>,
+    mut bullet_pool: ResMut<BulletPool>,
+    collision_grid: Res<CollisionGrid>,
+    enemy_shoots: Query<(&Transform, &Hitbox), With<FromEnemyShoot>>,
+    mut player: Query<(Entity, &Transform, &Hitbox, &mut Player), Without<Invulnerable>>,
     mut player_status: ResMut<PlayerStatus>,
+    mut sound_events: EventWriter<SoundEvent>,
+    mut particle_events: EventWriter<ParticleEvent>,
+    mut candidates: Local<Vec<Entity>>,
 ) {
-    if let Ok((player_entity, player_transform, player)) = player.get_single() {
-        for (enemy_shoot_entity, enemy_shoot_transform) in enemy_shoots.iter() {
-            if player.is_enable {
-                let is_collide = collide(
-                    player_transform.translation,
-                    Vec2::new(PLAYER_RADIUS, PLAYER_RADIUS),
-                    enemy_shoot_transform.translation,
-                    Vec2::new(SHOOT_RADIUS, SHOOT_RADIUS));
+    if let Ok((player_entity, player_transform, player_hitbox, mut player)) = player.get_single_mut() {
+        collision_grid.enemy_shoots.query(hitbox_bounds(player_hitbox, player_transform.translation.truncate()), &mut candidates);
 
-                if is_collide != None {
-                    commands.entity(player_entity).despawn();
+        for enemy_shoot_entity in candidates.iter().copied() {
+            let Ok((enemy_shoot_transform, enemy_shoot_hitbox)) = enemy_shoots.get(enemy_shoot_entity) else {
+                continue;
+            };
 
-                    player_status.is_spawn = true;
-                    player_status.spawn_timer = Timer::from_seconds(3.0, TimerMode::Once);
+            if player.is_enable && is_collide(player_transform, player_hitbox, enemy_shoot_transform, enemy_shoot_hitbox) {
+                if BOMB_AUTO && player_status.request_bomb() {
+                    // covered until the bomb goes off next
+                    commands.entity(player_entity).insert(Invulnerable::new(BOMB_INVULNERABLE_SECONDS));
+                } else {
+                    commands.entity(player_entity).despawn_recursive();
+                    player.is_enable = false;
 
-                    // player bang
-                    commands.spawn(ShowBangPoint {
-                        x: player_transform.translation.x,
-                        y: player_transform.translation.y,
-                    });
+                    player_status.miss();
+                    sound_events.send(SoundEvent::PlayerDefeated);
 
-                    commands.entity(enemy_shoot_entity).despawn();
+                    // player bang
+                    particle_events.send(ParticleEvent { position: player_transform.translation.truncate(), emitter: ParticleEmitter::PLAYER_DEFEATED });
                 }
+
+                bullet_pool.release(&mut commands, enemy_shoot_entity);
+                break;
             }
         }
     }
 }
 
+// near misses, a shot counts once and never in the tick it hits
+#[allow(clippy::type_complexity)]
+fn graze_system(
+    mut commands: Commands,
+    collision_grid: Res<CollisionGrid>,
+    enemy_shoots: Query<(&Transform, &Hitbox), (With<FromEnemyShoot>, Without<Grazed>)>,
+    player: Query<(&Transform, &Hitbox, &Player), Without<Invulnerable>>,
+    mut player_status: ResMut<PlayerStatus>,
+    mut candidates: Local<Vec<Entity>>,
+) {
+    if let Ok((player_transform, player_hitbox, player)) = player.get_single() {
+        if !player.is_enable {
+            return;
+        }
+
+        let graze_hitbox = Hitbox::circle(GRAZE_RADIUS);
+        collision_grid.enemy_shoots.query(hitbox_bounds(&graze_hitbox, player_transform.translation.truncate()), &mut candidates);
+
+        for enemy_shoot_entity in candidates.iter().copied() {
+            let Ok((enemy_shoot_transform, enemy_shoot_hitbox)) = enemy_shoots.get(enemy_shoot_entity) else {
+                continue;
+            };
+
+            if is_collide(player_transform, &graze_hitbox, enemy_shoot_transform, enemy_shoot_hitbox)
+                && !is_collide(player_transform, player_hitbox, enemy_shoot_transform, enemy_shoot_hitbox)
+            {
+                commands.entity(enemy_shoot_entity).insert(Grazed);
+                player_status.graze();
+
+                commands.spawn((
+                    SpatialBundle::from_transform(Transform::from_translation(enemy_shoot_transform.translation.truncate().extend(2.0))),
+                    GrazeSpark {
+                        timer: Timer::from_seconds(GRAZE_SPARK_SECONDS, TimerMode::Once),
+                    },
+                    OnGameScreen,
+                ));
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn player_enemy_collision_system(
     mut commands: Commands,
-    player: Query<(Entity, &Transform, &Player), With<Player>>,
-    enemy: Query<(Entity, &Transform), With<Enemy>>,
+    collision_grid: Res<CollisionGrid>,
+    mut player: Query<(Entity, &Transform, &Hitbox, &mut Player), Without<Invulnerable>>,
+    enemy: Query<(&Transform, &Hitbox, &Enemy, Has<Boss>)>,
     mut player_status: ResMut<PlayerStatus>,
+    mut defeated_events: EventWriter<EnemyDefeatedEvent>,
+    mut sound_events: EventWriter<SoundEvent>,
+    mut particle_events: EventWriter<ParticleEvent>,
+    mut candidates: Local<Vec<Entity>>,
 ) {
-    if let Ok((player_entity, player_transform, player)) = player.get_single() {
-        for (enemy_entity, enemy_transform) in enemy.iter() {
-            if player.is_enable {
-                let is_collide = collide(
-                    player_transform.translation,
-                    Vec2::new(PLAYER_RADIUS, PLAYER_RADIUS),
-                    enemy_transform.translation,
-                    Vec2::new(ENEMY_RADIUS, ENEMY_RADIUS));
-
-                if is_collide != None {
-                    commands.entity(player_entity).despawn();
-                    player_status.is_spawn = true;
-                    player_status.spawn_timer = Timer::from_seconds(3.0, TimerMode::Once);
-                    commands.spawn(ShowBangPoint {
-                        x: player_transform.translation.x,
-                        y: player_transform.translation.y,
-                    });
+    if let Ok((player_entity, player_transform, player_hitbox, mut player)) = player.get_single_mut() {
+        collision_grid.enemies.query(hitbox_bounds(player_hitbox, player_transform.translation.truncate()), &mut candidates);
+
+        for enemy_entity in candidates.iter().copied() {
+            let Ok((enemy_transform, enemy_hitbox, enemy, is_boss)) = enemy.get(enemy_entity) else {
+                continue;
+            };
+
+            if player.is_enable && is_collide(player_transform, player_hitbox, enemy_transform, enemy_hitbox) {
+                // the bomb deals with the enemy instead
+                if BOMB_AUTO && player_status.request_bomb() {
+                    commands.entity(player_entity).insert(Invulnerable::new(BOMB_INVULNERABLE_SECONDS));
+                    break;
+                }
 
+                commands.entity(player_entity).despawn_recursive();
+                player.is_enable = false;
+                player_status.miss();
+                sound_events.send(SoundEvent::PlayerDefeated);
+                particle_events.send(ParticleEvent { position: player_transform.translation.truncate(), emitter: ParticleEmitter::PLAYER_DEFEATED });
+
+                // the boss survives ramming
+                if !is_boss {
                     commands.entity(enemy_entity).despawn();
-                    commands.spawn(ShowBangPoint {
-                        x: enemy_transform.translation.x,
-                        y: enemy_transform.translation.y,
-                    });
+                    let x = enemy_transform.translation.x;
+                    let y = enemy_transform.translation.y;
+                    particle_events.send(ParticleEvent { position: Vec2::new(x, y), emitter: ParticleEmitter::ENEMY_DEFEATED });
+                    defeated_events.send(EnemyDefeatedEvent { x, y, drop_chance: enemy.drop_chance });
                 }
+                break;
             }
         }
     }
-}
\ No newline at end of file
+}
+
+fn is_collide(a_transform: &Transform, a: &Hitbox, b_transform: &Transform, b: &Hitbox) -> bool {
+    hitbox_intersects(a, a_transform.translation.truncate(), b, b_transform.translation.truncate())
+}
+
+// circles and capsules are a segment plus a radius, a circle's segment has zero length
+enum HitboxCore {
+    Segment(Vec2, Vec2, f32),
+    Box(Vec2, Vec2),
+}
+
+impl HitboxCore {
+    fn new(hitbox: &Hitbox, position: Vec2) -> Self {
+        let center = position + hitbox.offset;
+        match hitbox.shape {
+            HitboxShape::Circle { radius } => HitboxCore::Segment(center, center, radius),
+            HitboxShape::Capsule { half_segment, radius } => HitboxCore::Segment(center - half_segment, center + half_segment, radius),
+            HitboxShape::Aabb { half_size } => HitboxCore::Box(center - half_size, center + half_size),
+        }
+    }
+}
+
+fn hitbox_bounds(hitbox: &Hitbox, position: Vec2) -> (Vec2, Vec2) {
+    match HitboxCore::new(hitbox, position) {
+        HitboxCore::Segment(a, b, radius) => (a.min(b) - Vec2::splat(radius), a.max(b) + Vec2::splat(radius)),
+        HitboxCore::Box(min, max) => (min, max),
+    }
+}
+
+// exact overlap test for any pair of shapes, touching counts as a hit
+pub fn hitbox_intersects(a: &Hitbox, a_position: Vec2, b: &Hitbox, b_position: Vec2) -> bool {
+    match (HitboxCore::new(a, a_position), HitboxCore::new(b, b_position)) {
+        (HitboxCore::Segment(a0, a1, a_radius), HitboxCore::Segment(b0, b1, b_radius)) => {
+            let radius = a_radius + b_radius;
+            segment_segment_distance_squared(a0, a1, b0, b1) <= radius * radius
+        }
+        (HitboxCore::Segment(p0, p1, radius), HitboxCore::Box(min, max))
+        | (HitboxCore::Box(min, max), HitboxCore::Segment(p0, p1, radius)) => {
+            segment_box_distance_squared(p0, p1, min, max) <= radius * radius
+        }
+        (HitboxCore::Box(a_min, a_max), HitboxCore::Box(b_min, b_max)) => {
+            a_min.x <= b_max.x && b_min.x <= a_max.x && a_min.y <= b_max.y && b_min.y <= a_max.y
+        }
+    }
+}
+
+fn point_segment_distance_squared(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let length_squared = ab.length_squared();
+    let t = if length_squared > 0.0 {
+        ((point - a).dot(ab) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (a + ab * t).distance_squared(point)
+}
+
+fn segments_cross(a0: Vec2, a1: Vec2, b0: Vec2, b1: Vec2) -> bool {
+    let a = a1 - a0;
+    let b = b1 - b0;
+    let d0 = a.perp_dot(b0 - a0);
+    let d1 = a.perp_dot(b1 - a0);
+    let d2 = b.perp_dot(a0 - b0);
+    let d3 = b.perp_dot(a1 - b0);
+    // collinear and touching cases come out as zero distance below
+    d0 * d1 < 0.0 && d2 * d3 < 0.0
+}
+
+// in 2d the closest pair of two disjoint segments always includes an endpoint
+fn segment_segment_distance_squared(a0: Vec2, a1: Vec2, b0: Vec2, b1: Vec2) -> f32 {
+    if segments_cross(a0, a1, b0, b1) {
+        return 0.0;
+    }
+    point_segment_distance_squared(a0, b0, b1)
+        .min(point_segment_distance_squared(a1, b0, b1))
+        .min(point_segment_distance_squared(b0, a0, a1))
+        .min(point_segment_distance_squared(b1, a0, a1))
+}
+
+fn point_box_distance_squared(point: Vec2, min: Vec2, max: Vec2) -> f32 {
+    point.clamp(min, max).distance_squared(point)
+}
+
+// slab test, t runs from a at 0 to b at 1
+fn segment_enters_box(a: Vec2, b: Vec2, min: Vec2, max: Vec2) -> bool {
+    let direction = b - a;
+    let (mut t_min, mut t_max) = (0.0_f32, 1.0_f32);
+    for axis in 0..2 {
+        if direction[axis] == 0.0 {
+            if a[axis] < min[axis] || a[axis] > max[axis] {
+                return false;
+            }
+            continue;
+        }
+        let t0 = (min[axis] - a[axis]) / direction[axis];
+        let t1 = (max[axis] - a[axis]) / direction[axis];
+        t_min = t_min.max(t0.min(t1));
+        t_max = t_max.min(t0.max(t1));
+        if t_min > t_max {
+            return false;
+        }
+    }
+    true
+}
+
+// outside the box the closest pair is a segment endpoint or a box corner
+fn segment_box_distance_squared(a: Vec2, b: Vec2, min: Vec2, max: Vec2) -> f32 {
+    if segment_enters_box(a, b, min, max) {
+        return 0.0;
+    }
+    [min, Vec2::new(min.x, max.y), max, Vec2::new(max.x, min.y)]
+        .iter()
+        .map(|corner| point_segment_distance_squared(*corner, a, b))
+        .fold(
+            point_box_distance_squared(a, min, max).min(point_box_distance_squared(b, min, max)),
+            f32::min,
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aabb(half_size: Vec2) -> Hitbox {
+        Hitbox { shape: HitboxShape::Aabb { half_size }, offset: Vec2::ZERO }
+    }
+
+    fn vertical(half_length: f32, radius: f32) -> Hitbox {
+        Hitbox::capsule(Vec2::new(0.0, half_length), radius)
+    }
+
+    fn horizontal(half_length: f32, radius: f32) -> Hitbox {
+        Hitbox::capsule(Vec2::new(half_length, 0.0), radius)
+    }
+
+    #[test]
+    fn circle_circle() {
+        let (a, b) = (Hitbox::circle(3.0), Hitbox::circle(2.0));
+        assert!(hitbox_intersects(&a, Vec2::ZERO, &b, Vec2::new(4.0, 0.0)));
+        // touching counts
+        assert!(hitbox_intersects(&a, Vec2::ZERO, &b, Vec2::new(3.0, 4.0)));
+        assert!(!hitbox_intersects(&a, Vec2::ZERO, &b, Vec2::new(3.0, 4.01)));
+
+        // the offset moves the shape, not the entity
+        let shifted = Hitbox { offset: Vec2::new(10.0, 0.0), ..Hitbox::circle(3.0) };
+        assert!(hitbox_intersects(&shifted, Vec2::ZERO, &b, Vec2::new(15.0, 0.0)));
+        assert!(!hitbox_intersects(&shifted, Vec2::ZERO, &b, Vec2::new(4.9, 0.0)));
+    }
+
+    #[test]
+    fn capsule_capsule_crossing() {
+        // thin enough that only the segments crossing can make them meet
+        let (a, b) = (vertical(5.0, 0.1), horizontal(5.0, 0.1));
+        assert!(hitbox_intersects(&a, Vec2::ZERO, &b, Vec2::ZERO));
+        assert!(hitbox_intersects(&a, Vec2::ZERO, &b, Vec2::new(3.0, -2.0)));
+        assert!(!hitbox_intersects(&a, Vec2::ZERO, &b, Vec2::new(0.0, 6.0)));
+
+        let diagonal = Hitbox::capsule(Vec2::new(4.0, 4.0), 0.1);
+        assert!(hitbox_intersects(&diagonal, Vec2::ZERO, &Hitbox::capsule(Vec2::new(4.0, -4.0), 0.1), Vec2::ZERO));
+    }
+
+    #[test]
+    fn capsule_capsule_parallel() {
+        let a = vertical(5.0, 2.0);
+        assert!(hitbox_intersects(&a, Vec2::ZERO, &a, Vec2::new(4.0, 0.0)));
+        assert!(hitbox_intersects(&a, Vec2::ZERO, &a, Vec2::new(4.0, 9.0)));
+        assert!(!hitbox_intersects(&a, Vec2::ZERO, &a, Vec2::new(4.5, 0.0)));
+
+        // collinear and overlapping
+        let line = horizontal(5.0, 0.0);
+        assert!(hitbox_intersects(&line, Vec2::ZERO, &line, Vec2::new(6.0, 0.0)));
+    }
+
+    #[test]
+    fn capsule_capsule_end_to_end() {
+        // segments end 2 apart, radii make up exactly the gap
+        let a = vertical(5.0, 1.0);
+        assert!(hitbox_intersects(&a, Vec2::ZERO, &a, Vec2::new(0.0, 12.0)));
+        assert!(!hitbox_intersects(&a, Vec2::ZERO, &a, Vec2::new(0.0, 12.5)));
+
+        // segments meeting at a point
+        let line = horizontal(5.0, 0.0);
+        assert!(hitbox_intersects(&line, Vec2::ZERO, &line, Vec2::new(10.0, 0.0)));
+    }
+
+    #[test]
+    fn zero_length_capsule_is_a_circle() {
+        let dot = Hitbox::capsule(Vec2::ZERO, 3.0);
+        let circle = Hitbox::circle(3.0);
+        for position in [Vec2::new(5.0, 0.0), Vec2::new(6.0, 0.0), Vec2::new(6.01, 0.0), Vec2::new(4.0, 4.0)] {
+            assert_eq!(
+                hitbox_intersects(&dot, Vec2::ZERO, &circle, position),
+                hitbox_intersects(&circle, Vec2::ZERO, &circle, position),
+            );
+        }
+        let both = Hitbox::capsule(Vec2::ZERO, 1.0);
+        assert!(hitbox_intersects(&both, Vec2::ZERO, &both, Vec2::new(2.0, 0.0)));
+        assert!(!hitbox_intersects(&both, Vec2::ZERO, &both, Vec2::new(2.01, 0.0)));
+    }
+
+    #[test]
+    fn capsule_aabb() {
+        let block = aabb(Vec2::new(2.0, 2.0));
+
+        // along an edge, touching counts
+        let a = vertical(5.0, 1.0);
+        assert!(hitbox_intersects(&a, Vec2::new(3.0, 0.0), &block, Vec2::ZERO));
+        assert!(!hitbox_intersects(&a, Vec2::new(3.5, 0.0), &block, Vec2::ZERO));
+
+        // a thin capsule straight through with both ends outside
+        let through = horizontal(10.0, 0.0);
+        assert!(hitbox_intersects(&through, Vec2::new(0.0, 1.0), &block, Vec2::ZERO));
+        assert!(hitbox_intersects(&through, Vec2::new(0.0, 2.0), &block, Vec2::ZERO));
+        assert!(!hitbox_intersects(&through, Vec2::new(0.0, 2.01), &block, Vec2::ZERO));
+
+        // diagonal grazing a corner, both ends outside
+        let diagonal = Hitbox::capsule(Vec2::new(3.0, -3.0), 0.0);
+        assert!(hitbox_intersects(&diagonal, Vec2::new(2.0, 2.0), &block, Vec2::ZERO));
+        assert!(!hitbox_intersects(&diagonal, Vec2::new(2.1, 2.1), &block, Vec2::ZERO));
+
+        // nearest to a corner, a 3-4-5 triangle away
+        assert!(hitbox_intersects(&block, Vec2::ZERO, &Hitbox::circle(5.0), Vec2::new(5.0, 6.0)));
+        assert!(!hitbox_intersects(&block, Vec2::ZERO, &Hitbox::circle(4.99), Vec2::new(5.0, 6.0)));
+
+        // wholly inside
+        assert!(hitbox_intersects(&Hitbox::circle(0.5), Vec2::ZERO, &block, Vec2::ZERO));
+    }
+
+    #[test]
+    fn aabb_aabb() {
+        let a = aabb(Vec2::new(1.0, 1.0));
+        let b = aabb(Vec2::new(2.0, 1.0));
+        assert!(hitbox_intersects(&a, Vec2::ZERO, &b, Vec2::new(2.0, 1.0)));
+        // edges and corners touching count
+        assert!(hitbox_intersects(&a, Vec2::ZERO, &b, Vec2::new(3.0, 0.0)));
+        assert!(hitbox_intersects(&a, Vec2::ZERO, &b, Vec2::new(3.0, 2.0)));
+        assert!(!hitbox_intersects(&a, Vec2::ZERO, &b, Vec2::new(3.01, 0.0)));
+        assert!(!hitbox_intersects(&a, Vec2::ZERO, &b, Vec2::new(0.0, 2.01)));
+    }
+
+    // four by four cells from the origin
+    fn grid() -> SpatialGrid {
+        SpatialGrid::new(Vec2::ZERO, Vec2::splat(COLLISION_CELL_SIZE * 4.0))
+    }
+
+    fn point(x: f32, y: f32) -> (Vec2, Vec2) {
+        (Vec2::new(x, y), Vec2::new(x, y))
+    }
+
+    fn query(grid: &SpatialGrid, bounds: (Vec2, Vec2)) -> Vec<Entity> {
+        let mut found = Vec::new();
+        grid.query(bounds, &mut found);
+        found
+    }
+
+    #[test]
+    fn grid_cell_borders() {
+        let (a, b, c) = (Entity::from_raw(1), Entity::from_raw(2), Entity::from_raw(3));
+        let border = COLLISION_CELL_SIZE;
+        let mut grid = grid();
+        grid.insert(a, point(border - 0.01, border - 0.01));
+        // a point on a border belongs to the cell above it
+        grid.insert(b, point(border, border));
+        // bounds across the border sit in all four cells
+        grid.insert(c, (Vec2::splat(border - 1.0), Vec2::splat(border + 1.0)));
+
+        assert_eq!(query(&grid, point(1.0, 1.0)), vec![a, c]);
+        assert_eq!(query(&grid, point(border, border)), vec![b, c]);
+        assert_eq!(query(&grid, point(border, 1.0)), vec![c]);
+        // reaching over the border finds both sides, each entity once
+        assert_eq!(query(&grid, (Vec2::splat(1.0), Vec2::splat(border))), vec![a, b, c]);
+        assert_eq!(query(&grid, point(border * 2.0, border * 2.0)), vec![]);
+
+        grid.clear();
+        assert_eq!(query(&grid, (Vec2::ZERO, Vec2::splat(border * 4.0))), vec![]);
+    }
+
+    #[test]
+    fn grid_off_the_playfield() {
+        let (a, b) = (Entity::from_raw(1), Entity::from_raw(2));
+        let size = COLLISION_CELL_SIZE * 4.0;
+        let mut grid = grid();
+        grid.insert(a, point(-100.0, -100.0));
+        grid.insert(b, point(size + 100.0, size * 0.5));
+
+        // outside lands in the nearest edge cell
+        assert_eq!(query(&grid, point(1.0, 1.0)), vec![a]);
+        assert_eq!(query(&grid, point(-5.0, -1000.0)), vec![a]);
+        assert_eq!(query(&grid, point(size - 1.0, size * 0.5)), vec![b]);
+        assert_eq!(query(&grid, point(size * 0.5, size * 0.5)), vec![]);
+        // bounds larger than the grid reach every cell
+        assert_eq!(query(&grid, (Vec2::splat(-size), Vec2::splat(size * 2.0))), vec![a, b]);
+    }
+}
@@ -1,22 +1,80 @@
 use std::time::Duration;
 
-use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use bevy::{prelude::*, ecs::system::EntityCommands, utils::HashSet};
 
-use crate::define::*;
+use crate::{define::*, input::{GameInput, GameButton}, is_verbose};
 
 pub struct CommonPlugin;
 
 impl Plugin for CommonPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(FixedUpdate, player_in_window_system)
-            .add_systems(Update, (
+            .init_resource::<BulletPool>()
+            .add_systems(FixedUpdate, (
+                player_in_window_system,
+                bullet_motion_system,
                 auto_move_system,
                 auto_despawn_system,
-                shoot_bang_system,
-                show_bang_system,
+                hit_flash_system,
+                graze_spark_system,
+                show_score_system,
+                score_popup_system,
+                bomb_wave_system,
+                show_bomb_system,
                 speed_control_system,
-            ));
+            ).chain().in_set(GameSet::Common).run_if(in_state(GameState::InGame)));
+    }
+}
+
+// bullets always carry this, released ones wait hidden in the pool instead of being despawned
+#[derive(Component)]
+pub struct Pooled;
+
+// what release strips from a bullet, anything the spawn bundles or an emitter's behaviours add
+pub type BulletComponents = (Velocity, Damage, Hitbox, AutoDespawn, FromPlayerShoot, FromEnemyShoot, Grazed, Acceleration, AngularVelocity, Homing, DelayedAim, OnGameScreen);
+
+#[derive(Resource, Default)]
+pub struct BulletPool {
+    free: Vec<Entity>,
+    is_free: HashSet<Entity>,
+    // components other plugins put on their bullets, stripped along with BulletComponents
+    extra: Vec<fn(&mut EntityCommands)>,
+}
+
+impl BulletPool {
+    // reuses a released bullet when there is one, the bundle carries the velocity, hitbox and owner
+    pub fn spawn(&mut self, commands: &mut Commands, translation: Vec3, bundle: impl Bundle) -> Entity {
+        let transform = Transform::from_translation(translation);
+        let entity = match self.free.pop() {
+            Some(entity) => {
+                self.is_free.remove(&entity);
+                commands.entity(entity).insert((transform, Visibility::Visible));
+                entity
+            }
+            None => commands.spawn((SpatialBundle::from_transform(transform), Pooled)).id(),
+        };
+        commands.entity(entity).insert((bundle, AutoDespawn, OnGameScreen));
+        entity
+    }
+
+    // safe to call more than once for the same bullet in a tick
+    pub fn release(&mut self, commands: &mut Commands, entity: Entity) {
+        if !self.is_free.insert(entity) {
+            return;
+        }
+        let mut bullet = commands.entity(entity);
+        bullet.remove::<BulletComponents>().insert(Visibility::Hidden);
+        for remove in self.extra.iter() {
+            remove(&mut bullet);
+        }
+        self.free.push(entity);
+    }
+
+    // for a plugin whose bullets carry a component of its own, e.g. a BulletML runner
+    pub fn register<T: Bundle>(&mut self) {
+        self.extra.push(|bullet| {
+            bullet.remove::<T>();
+        });
     }
 }
 
@@ -49,102 +107,343 @@ fn player_in_window_system(
     }
 }
 
-fn auto_move_system(
-    mut query: Query<(&mut Transform, &mut Velocity)>,
-    time: Res<Time<Virtual>>,
+// every step works off the fixed tick, so the same inputs always give the same paths
+#[allow(clippy::type_complexity)]
+fn bullet_motion_system(
+    mut query: Query<(&Transform, &mut Velocity, Option<&Acceleration>, Option<&AngularVelocity>, Option<&Homing>, Option<&mut DelayedAim>, Has<FromEnemyShoot>), Or<(With<FromEnemyShoot>, With<FromPlayerShoot>)>>,
+    player: Query<&Transform, With<Player>>,
+    enemies: Query<&Transform, With<Enemy>>,
+    time: Res<Time>,
 ) {
-    if !time.is_paused() {
-        for (mut transform, velocity) in query.iter_mut() {
-            let x = transform.translation.x;
-            let y = transform.translation.y;
+    let delta = time.delta_seconds();
+    let player = player.get_single().ok().map(|player| player.translation.truncate());
+
+    for (transform, mut velocity, acceleration, angular_velocity, homing, delayed_aim, is_enemy_shoot) in query.iter_mut() {
+        let position = transform.translation.truncate();
+        let target = if homing.is_none() && delayed_aim.is_none() {
+            None
+        } else if is_enemy_shoot {
+            player
+        } else {
+            enemies.iter()
+                .map(|enemy| enemy.translation.truncate())
+                .min_by(|a, b| a.distance_squared(position).total_cmp(&b.distance_squared(position)))
+        };
+        let mut direction = Vec2::new(velocity.x, velocity.y);
+
+        if let Some(mut delayed_aim) = delayed_aim {
+            if !delayed_aim.stop.finished() {
+                if delayed_aim.stop.tick(time.delta()).just_finished() {
+                    direction = Vec2::ZERO;
+                }
+            } else if !delayed_aim.wait.finished() {
+                direction = Vec2::ZERO;
+                if delayed_aim.wait.tick(time.delta()).just_finished() {
+                    // straight on down or up the screen with nothing to aim at
+                    let heading = target
+                        .and_then(|target| (target - position).try_normalize())
+                        .unwrap_or(if is_enemy_shoot { Vec2::NEG_Y } else { Vec2::Y });
+                    direction = heading * delayed_aim.speed;
+                }
+            }
+        }
+
+        if let Some(acceleration) = acceleration {
+            let speed = (direction.length() + acceleration.rate * delta).clamp(0.0, acceleration.max_speed);
+            direction = direction.normalize_or_zero() * speed;
+        }
 
-            let x = x + velocity.x;
-            let y = y + velocity.y;
+        if let Some(angular_velocity) = angular_velocity {
+            direction = Vec2::from_angle(angular_velocity.0 * delta).rotate(direction);
+        }
 
-            transform.translation = Vec3::new(x, y, transform.translation.z);
+        if let (Some(homing), Some(target)) = (homing, target) {
+            if direction != Vec2::ZERO && target != position {
+                let max_turn = homing.turn_rate * delta;
+                let turn = direction.angle_between(target - position).clamp(-max_turn, max_turn);
+                direction = Vec2::from_angle(turn).rotate(direction);
+            }
         }
+
+        velocity.x = direction.x;
+        velocity.y = direction.y;
+    }
+}
+
+fn auto_move_system(
+    mut query: Query<(&mut Transform, &Velocity)>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_seconds();
+    for (mut transform, velocity) in query.iter_mut() {
+        let x = transform.translation.x + velocity.x * delta;
+        let y = transform.translation.y + velocity.y * delta;
+
+        transform.translation = Vec3::new(x, y, transform.translation.z);
     }
 }
 
 fn auto_despawn_system(
     mut commands: Commands,
-    query: Query<(Entity, &Transform), With<AutoDespawn>>,
+    query: Query<(Entity, &Transform, Has<Pooled>), With<AutoDespawn>>,
     window_size_limit: Res<WindowSizeLimit>,
+    mut bullet_pool: ResMut<BulletPool>,
 ) {
     let margin = WINDOW_SIZE_MARGIN;
-    for (entity, transform) in query.iter() {
+    for (entity, transform, is_pooled) in query.iter() {
         if transform.translation.x > window_size_limit.right + margin
             || transform.translation.x < window_size_limit.left - margin
             || transform.translation.y > window_size_limit.top + margin
             || transform.translation.y < window_size_limit.bottom - margin
         {
+            if is_pooled {
+                bullet_pool.release(&mut commands, entity);
+            } else {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+fn hit_flash_system(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut HitFlash)>,
+    time: Res<Time>,
+) {
+    for (entity, mut hit_flash) in query.iter_mut() {
+        if hit_flash.timer.tick(time.delta()).just_finished() {
+            commands.entity(entity).remove::<HitFlash>();
+        }
+    }
+}
+
+fn graze_spark_system(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut GrazeSpark)>,
+    time: Res<Time>,
+) {
+    for (entity, mut graze_spark) in query.iter_mut() {
+        if graze_spark.timer.tick(time.delta()).just_finished() {
             commands.entity(entity).despawn();
         }
     }
 }
 
-fn shoot_bang_system(
+// grows from the player out to BOMB_WAVE_RADIUS
+fn bomb_wave_system(
     mut commands: Commands,
-    mut query: Query<(Entity, &mut ShootBang)>,
-    time: Res<Time<Virtual>>,
+    mut query: Query<(Entity, &mut Transform, &mut BombWave)>,
+    time: Res<Time>,
 ) {
-    for (entity, mut shoot_bang) in query.iter_mut() {
-        if shoot_bang.timer.tick(time.delta()).just_finished() {
+    for (entity, mut transform, mut bomb_wave) in query.iter_mut() {
+        if bomb_wave.timer.tick(time.delta()).just_finished() {
             commands.entity(entity).despawn_recursive();
+            continue;
         }
+        transform.scale = Vec3::splat(bomb_wave.timer.percent() * BOMB_WAVE_RADIUS);
     }
 }
 
-fn show_bang_system(
+fn show_bomb_system(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    query: Query<(Entity, &ShowBangPoint)>,
+    query: Query<(Entity, &ShowBombPoint)>,
 ) {
-    for (entity, show_bang_point) in query.iter() {
+    for (entity, show_bomb_point) in query.iter() {
         commands.spawn((
-            MaterialMesh2dBundle {
-                mesh: meshes.add(shape::Quad::new(Vec2::new(20.0, 50.0)).into()).into(),
-                material: materials.add(ColorMaterial::from(Color::RED)),
-                transform: Transform {
-                    translation: Vec3::new(show_bang_point.x, show_bang_point.y, 1.0),
-                    rotation: Quat::from_rotation_z(400.0),
-                    ..default()
-                },
+            SpatialBundle::from_transform(Transform {
+                translation: Vec3::new(show_bomb_point.x, show_bomb_point.y, 2.0),
+                scale: Vec3::ZERO,
                 ..default()
+            }),
+            BombWave {
+                timer: Timer::new(Duration::from_secs_f32(BOMB_WAVE_SECONDS), TimerMode::Once),
             },
-            ShootBang {
-                timer: Timer::new(Duration::from_secs_f32(0.5), TimerMode::Once),
-            }
-        )).with_children(|p| {
-            p.spawn(MaterialMesh2dBundle {
-                mesh: meshes.add(shape::Quad::new(Vec2::new(20.0, 50.0)).into()).into(),
-                material: materials.add(ColorMaterial::from(Color::RED)),
-                transform: Transform {
-                    rotation: Quat::from_rotation_z(20.0),
-                    ..default()
-                },
-                ..default()
-            });
-        });
+            OnGameScreen,
+        ));
+
+        commands.entity(entity).despawn();
+    }
+}
+
+fn show_score_system(
+    mut commands: Commands,
+    query: Query<(Entity, &ShowScorePoint)>,
+) {
+    for (entity, show_score_point) in query.iter() {
+        commands.spawn((
+            SpatialBundle::from_transform(Transform::from_xyz(show_score_point.x, show_score_point.y, 3.0)),
+            Velocity { x: 0.0, y: SCORE_POPUP_VELOCITY },
+            ScorePopup {
+                point: show_score_point.point,
+                timer: Timer::new(Duration::from_secs_f32(SCORE_POPUP_SECONDS), TimerMode::Once),
+            },
+            OnGameScreen,
+        ));
 
         commands.entity(entity).despawn();
     }
 }
 
+fn score_popup_system(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut ScorePopup)>,
+    time: Res<Time>,
+) {
+    for (entity, mut score_popup) in query.iter_mut() {
+        if score_popup.timer.tick(time.delta()).just_finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
 fn speed_control_system(
-    input: Res<Input<KeyCode>>,
+    input: Res<GameInput>,
     mut speed_control: ResMut<SpeedControl>,
 ) {
     // speed up
-    if input.just_pressed(KeyCode::A) {
+    if input.tick_just_pressed(GameButton::SpeedUp) {
         speed_control.value *= 1.2;
-        println!("speed: {}", speed_control.value);
+        if is_verbose() {
+            println!("speed: {}", speed_control.value);
+        }
     }
 
     // speed down
-    if input.just_pressed(KeyCode::Z) {
+    if input.tick_just_pressed(GameButton::SpeedDown) {
         speed_control.value *= 0.8;
-        println!("speed: {}", speed_control.value);
+        if is_verbose() {
+            println!("speed: {}", speed_control.value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::system::CommandQueue, time::TimeUpdateStrategy};
+
+    use super::*;
+
+    const PLAYER_POSITION: Vec2 = Vec2::new(0.0, -200.0);
+    const ENEMY_POSITION: Vec2 = Vec2::new(100.0, 200.0);
+
+    // just the motion step and the move after it, one fixed frame per update
+    fn run_shots(frames: usize) -> (App, Vec<Entity>) {
+        let mut app = App::new();
+        app
+            .add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(HEADLESS_FRAME_SECONDS)))
+            .add_systems(Update, (bullet_motion_system, auto_move_system).chain());
+
+        app.world.spawn((
+            Transform::from_translation(PLAYER_POSITION.extend(0.0)),
+            Player { x: PLAYER_POSITION.x, y: PLAYER_POSITION.y, z: 0.0, is_enable: true, shoot_type: ShootType::Normal, is_focus: false },
+        ));
+        app.world.spawn((
+            Transform::from_translation(ENEMY_POSITION.extend(0.0)),
+            Enemy { shoot_interval: Timer::default(), point: 1.0, drop_chance: 0.0 },
+        ));
+
+        let shots = vec![
+            spawn_shot(&mut app.world, Vec2::new(0.0, 200.0), Vec2::new(100.0, 0.0), (FromEnemyShoot, Homing { turn_rate: 2.0 })),
+            spawn_shot(&mut app.world, Vec2::new(0.0, 100.0), Vec2::new(0.0, -50.0), (FromEnemyShoot, (Acceleration { rate: 100.0, max_speed: 80.0 }, AngularVelocity(1.0)))),
+            spawn_shot(&mut app.world, Vec2::new(-100.0, 200.0), Vec2::new(0.0, -100.0), (FromEnemyShoot, DelayedAim::new(0.1, 0.1, 120.0))),
+            spawn_shot(&mut app.world, PLAYER_POSITION, Vec2::new(0.0, 300.0), (FromPlayerShoot, Homing { turn_rate: 2.0 })),
+            spawn_shot(&mut app.world, PLAYER_POSITION, Vec2::new(0.0, 300.0), (FromPlayerShoot, DelayedAim::new(0.1, 0.1, 120.0))),
+        ];
+
+        app.finish();
+        app.cleanup();
+        for _ in 0..frames {
+            app.update();
+        }
+        (app, shots)
+    }
+
+    fn spawn_shot(world: &mut World, position: Vec2, velocity: Vec2, bundle: impl Bundle) -> Entity {
+        world.spawn((Transform::from_translation(position.extend(0.0)), Velocity { x: velocity.x, y: velocity.y }, bundle)).id()
+    }
+
+    fn heading(app: &App, shot: Entity) -> Vec2 {
+        let velocity = app.world.get::<Velocity>(shot).unwrap();
+        Vec2::new(velocity.x, velocity.y).normalize_or_zero()
+    }
+
+    fn positions(app: &App, shots: &[Entity]) -> Vec<Vec3> {
+        shots.iter().map(|shot| app.world.get::<Transform>(*shot).unwrap().translation).collect()
+    }
+
+    #[test]
+    fn shots_steer_toward_their_owners_target() {
+        let (app, shots) = run_shots(30);
+        let position = |shot| app.world.get::<Transform>(shot).unwrap().translation.truncate();
+
+        // enemy shots go for the player, player shots for the nearest enemy
+        assert!(heading(&app, shots[0]).y < 0.0);
+        assert!(heading(&app, shots[3]).x > 0.0);
+
+        // both delayed shots have set off at the target
+        let enemy_aim = (PLAYER_POSITION - position(shots[2])).normalize();
+        assert!(heading(&app, shots[2]).dot(enemy_aim) > 0.99);
+        let player_aim = (ENEMY_POSITION - position(shots[4])).normalize();
+        assert!(heading(&app, shots[4]).dot(player_aim) > 0.99);
+
+        // sped up to its cap and turned off straight down
+        let velocity = app.world.get::<Velocity>(shots[1]).unwrap();
+        assert!((Vec2::new(velocity.x, velocity.y).length() - 80.0).abs() < 1e-3);
+        assert!(velocity.x > 0.0);
+    }
+
+    // stands in for a component another plugin registers, like a BulletML runner
+    #[derive(Component)]
+    struct Scripted;
+
+    #[test]
+    fn released_bullets_are_stripped_and_reused() {
+        let mut world = World::new();
+        let mut bullet_pool = BulletPool::default();
+        bullet_pool.register::<Scripted>();
+        let mut queue = CommandQueue::default();
+
+        let bullet = {
+            let mut commands = Commands::new(&mut queue, &world);
+            let bullet = bullet_pool.spawn(&mut commands, Vec3::ZERO, (Velocity { x: 0.0, y: -1.0 }, FromEnemyShoot, Homing { turn_rate: 1.0 }, Scripted));
+            // releasing twice in a tick frees it once
+            bullet_pool.release(&mut commands, bullet);
+            bullet_pool.release(&mut commands, bullet);
+            bullet
+        };
+        queue.apply(&mut world);
+
+        assert_eq!(bullet_pool.free, vec![bullet]);
+        let released = world.entity(bullet);
+        assert!(!released.contains::<Velocity>());
+        assert!(!released.contains::<FromEnemyShoot>());
+        assert!(!released.contains::<Homing>());
+        assert!(!released.contains::<AutoDespawn>());
+        assert!(!released.contains::<Scripted>());
+        assert!(released.contains::<Pooled>());
+        assert_eq!(*released.get::<Visibility>().unwrap(), Visibility::Hidden);
+
+        let reused = {
+            let mut commands = Commands::new(&mut queue, &world);
+            bullet_pool.spawn(&mut commands, Vec3::new(1.0, 2.0, 0.0), (Velocity { x: 0.0, y: 1.0 }, FromPlayerShoot))
+        };
+        queue.apply(&mut world);
+
+        assert_eq!(reused, bullet);
+        assert!(bullet_pool.free.is_empty());
+        let reused = world.entity(reused);
+        assert!(reused.contains::<FromPlayerShoot>());
+        assert!(reused.contains::<AutoDespawn>());
+        assert!(!reused.contains::<FromEnemyShoot>());
+        assert_eq!(reused.get::<Transform>().unwrap().translation, Vec3::new(1.0, 2.0, 0.0));
+        assert_eq!(*reused.get::<Visibility>().unwrap(), Visibility::Visible);
+    }
+
+    #[test]
+    fn same_shots_same_paths() {
+        let (first, first_shots) = run_shots(120);
+        let (second, second_shots) = run_shots(120);
+        assert_eq!(positions(&first, &first_shots), positions(&second, &second_shots));
     }
 }
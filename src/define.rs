@@ -1,17 +1,122 @@
-use bevy::{prelude::{Resource, Component, Vec3}, time::Timer};
+use bevy::{prelude::{Resource, Component, States, SystemSet, Vec2, Vec3}, time::{Timer, TimerMode}};
 
 pub const WINDOW_SIZE_WIDTH: f32 = 400.0;
 pub const WINDOW_SIZE_HEIGHT: f32 = 720.0;
 pub const WINDOW_SIZE_MARGIN: f32 = 100.0;
 
+pub const HEADLESS_FRAME_SECONDS: f32 = 1.0 / 60.0;
+
+// gameplay ticks per second, velocities are in units per second
+pub const FIXED_UPDATE_HZ: f64 = 60.0;
+
+// share of the stick's travel ignored around the center
+pub const GAMEPAD_DEADZONE: f32 = 0.2;
+// past this the stick also presses the direction buttons
+pub const GAMEPAD_STICK_PRESS: f32 = 0.5;
+
 pub const PLAYER_RADIUS: f32 = 14.0;
-pub const PLAYER_VELOCITY: f32 = 3.0;
+// only the core of the ship can be hit
+pub const PLAYER_HITBOX_RADIUS: f32 = 3.0;
+pub const PLAYER_VELOCITY: f32 = 180.0;
+pub const PLAYER_FOCUS_VELOCITY: f32 = 80.0;
+pub const PLAYER_START_VELOCITY: f32 = 300.0;
+pub const PLAYER_LIVES: u32 = 3;
+pub const PLAYER_RESPAWN_SECONDS: f32 = 3.0;
+
+// enemy shots passing this close count once as a graze
+pub const GRAZE_RADIUS: f32 = 24.0;
+pub const GRAZE_SCORE: f32 = 0.2;
+// grazes to fill the meter, emptied on a miss
+pub const GRAZE_METER_MAX: u32 = 50;
+pub const GRAZE_SPARK_SECONDS: f32 = 0.2;
+
+// items collected to reach each shot type: normal, double, triple
+pub const POWER_LEVELS: [u32; 3] = [0, 4, 10];
+pub const POWER_LOSS_ON_MISS: u32 = 3;
+
+// bomb stock, refilled on every life
+pub const PLAYER_BOMBS: u32 = 3;
+pub const BOMB_DAMAGE: f32 = 10.0;
+pub const BOMB_INVULNERABLE_SECONDS: f32 = 2.0;
+// spend a bomb instead of a life when hit
+pub const BOMB_AUTO: bool = true;
+pub const BOMB_WAVE_SECONDS: f32 = 0.5;
+pub const BOMB_WAVE_RADIUS: f32 = 400.0;
+pub const ITEM_RADIUS: f32 = 8.0;
+pub const ITEM_VELOCITY: f32 = 60.0;
+
+// kills within the window keep the chain going, every few kills raise the multiplier
+pub const CHAIN_WINDOW_SECONDS: f32 = 2.0;
+pub const CHAIN_KILLS_PER_STEP: u32 = 5;
+pub const CHAIN_MULTIPLIER_MAX: u32 = 8;
+pub const SCORE_POPUP_SECONDS: f32 = 0.6;
+pub const SCORE_POPUP_VELOCITY: f32 = 40.0;
+
+// score thresholds giving an extra life
+pub const EXTEND_SCORES: [f32; 3] = [50.0, 150.0, 300.0];
 
 pub const ENEMY_RADIUS: f32 = 15.0;
-pub const ENEMY_SPAWN_DURATION_SECONDS: f32 = 1.0;
+pub const BOSS_RADIUS: f32 = 40.0;
+// between boss phases, shots are absorbed while it moves back home
+pub const BOSS_TRANSITION_SECONDS: f32 = 2.0;
 
-pub const SHOOT_VELOCITY: f32 = 3.0;
+pub const SHOOT_VELOCITY: f32 = 180.0;
 pub const SHOOT_RADIUS: f32 = 5.0;
+// sideways speed of the outer double and triple shots, times the shot speed
+pub const SHOOT_SPREAD: f32 = 0.5;
+pub const SHOOT_FOCUS_SPREAD: f32 = 0.15;
+// damage per bullet, spread shots trade power for coverage
+pub const SHOOT_DAMAGE_NORMAL: f32 = 2.0;
+pub const SHOOT_DAMAGE_DOUBLE: f32 = 1.0;
+pub const SHOOT_DAMAGE_TRIPLE: f32 = 1.0;
+pub const HIT_FLASH_SECONDS: f32 = 0.08;
+
+// live particles at most, bursts past it are cut short
+pub const PARTICLE_MAX: usize = 4096;
+
+// copies of one sound allowed to play at once
+pub const SOUND_MAX_VOICES: usize = 4;
+
+// $rank seen by BulletML scripts, 0.0 to 1.0
+pub const BULLETML_RANK: f32 = 0.5;
+// steps a script runs at most in one frame, so a repeat without a wait cannot hang
+pub const BULLETML_STEPS_PER_FRAME: u32 = 10000;
+
+// broadphase grid cell, a few bullets wide
+pub const COLLISION_CELL_SIZE: f32 = 32.0;
+
+pub const GAME_OVER_SCREEN_SECONDS: f32 = 3.0;
+pub const CONTINUE_ENABLE: bool = true;
+pub const CONTINUE_SECONDS: f32 = 10.0;
+
+////////// State
+
+#[derive(States, Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum GameState {
+    #[default]
+    Loading,
+    Title,
+    KeyConfig,
+    InGame,
+    Paused,
+    GameOver,
+    NameEntry,
+    Results,
+}
+
+////////// SystemSet
+
+// gameplay runs in this order every fixed tick, replays depend on it
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GameSet {
+    Timer,
+    Enemy,
+    Player,
+    Bomb,
+    Common,
+    Collision,
+    Item,
+}
 
 ////////// Resource
 
@@ -27,6 +132,12 @@ impl WindowSizeLimit {
     pub fn new(top: f32, bottom: f32, right: f32, left: f32) -> Self {
         Self { top, bottom, right, left }
     }
+
+    pub fn from_size(width: f32, height: f32) -> Self {
+        let width = width / 2.0;
+        let height = height / 2.0;
+        Self::new(height, -height, width, -width)
+    }
 }
 
 #[derive(Resource)]
@@ -34,12 +145,109 @@ pub struct PlayerStatus {
     pub is_spawn: bool,
     pub spawn_timer: Timer,
     pub score: f32,
+    pub lives: u32,
+    pub extend_index: usize,
+    pub continues: u32,
+    // power items collected
+    pub power: u32,
+    pub bombs: u32,
+    // set by the bomb button or an auto bomb, handled by the bomb plugin
+    pub is_bomb: bool,
+    // grazes this run, kept through continues for the results screen
+    pub graze: u32,
+    pub graze_meter: u32,
+    // kills in the current chain, the window restarts on every kill
+    pub chain: u32,
+    pub chain_timer: Timer,
 }
 
-#[derive(Resource)]
-pub struct EnemySpawn {
-    pub counter: u32,
-    pub timer: Timer,
+impl Default for PlayerStatus {
+    fn default() -> Self {
+        Self {
+            is_spawn: true,
+            spawn_timer: Timer::from_seconds(1.0, TimerMode::Once),
+            score: 0.0,
+            lives: PLAYER_LIVES,
+            extend_index: 0,
+            continues: 0,
+            power: 0,
+            bombs: PLAYER_BOMBS,
+            is_bomb: false,
+            graze: 0,
+            graze_meter: 0,
+            chain: 0,
+            chain_timer: Timer::from_seconds(CHAIN_WINDOW_SECONDS, TimerMode::Once),
+        }
+    }
+}
+
+impl PlayerStatus {
+    pub fn miss(&mut self) {
+        self.lives = self.lives.saturating_sub(1);
+        self.power = self.power.saturating_sub(POWER_LOSS_ON_MISS);
+        self.bombs = PLAYER_BOMBS;
+        self.graze_meter = 0;
+        self.chain = 0;
+        self.is_spawn = true;
+        self.spawn_timer = Timer::from_seconds(PLAYER_RESPAWN_SECONDS, TimerMode::Once);
+    }
+
+    pub fn continue_game(&mut self) {
+        self.score = 0.0;
+        self.lives = PLAYER_LIVES;
+        self.extend_index = 0;
+        self.power = 0;
+        self.bombs = PLAYER_BOMBS;
+        self.graze_meter = 0;
+        self.chain = 0;
+        self.continues += 1;
+        self.is_spawn = true;
+        self.spawn_timer = Timer::from_seconds(1.0, TimerMode::Once);
+    }
+
+    // asks for a bomb, false when there is none to spend
+    pub fn request_bomb(&mut self) -> bool {
+        if self.bombs == 0 || self.is_bomb {
+            return false;
+        }
+        self.is_bomb = true;
+        true
+    }
+
+    pub fn graze(&mut self) {
+        self.graze += 1;
+        self.graze_meter = (self.graze_meter + 1).min(GRAZE_METER_MAX);
+        self.score += GRAZE_SCORE;
+    }
+
+    pub fn chain_multiplier(&self) -> u32 {
+        (1 + self.chain / CHAIN_KILLS_PER_STEP).min(CHAIN_MULTIPLIER_MAX)
+    }
+
+    // scores a kill at the current multiplier and returns the points given
+    pub fn kill(&mut self, point: f32) -> f32 {
+        self.chain += 1;
+        self.chain_timer.reset();
+        let point = point * self.chain_multiplier() as f32;
+        self.score += point;
+        point
+    }
+
+    // a lapsed window drops the multiplier one step and gives the rest another window
+    pub fn chain_decay(&mut self) {
+        self.chain = (self.chain_multiplier() - 1).saturating_sub(1) * CHAIN_KILLS_PER_STEP;
+        self.chain_timer.reset();
+    }
+
+    pub fn shoot_type(&self) -> ShootType {
+        if self.power >= POWER_LEVELS[2] {
+            ShootType::Triple
+        } else if self.power >= POWER_LEVELS[1] {
+            ShootType::Double
+        } else {
+            ShootType::Normal
+        }
+    }
 }
 
 #[derive(Resource)]
@@ -47,6 +255,14 @@ pub struct SpeedControl {
     pub value: f32,
 }
 
+// start the first run without waiting on the title screen
+#[derive(Resource)]
+pub struct SkipTitle;
+
+// --cheat, N/D/T pick the shot type directly
+#[derive(Resource)]
+pub struct Cheats;
+
 #[derive(Resource)]
 pub struct GameTimer {
     pub timer: Timer,
@@ -55,18 +271,67 @@ pub struct GameTimer {
 
 ////////// Component
 
+// units per second
 #[derive(Component)]
 pub struct Velocity {
     pub x: f32,
     pub y: f32,
 }
 
+// bullet behaviours, they change Velocity before auto_move_system moves by it. enemy shots
+// go for the player, player shots for the nearest enemy
+
+// speeds up along its heading, rate in units per second each second
+#[derive(Component, Clone, Copy)]
+pub struct Acceleration {
+    pub rate: f32,
+    pub max_speed: f32,
+}
+
+// turns the heading, radians per second, positive is counterclockwise
+#[derive(Component, Clone, Copy)]
+pub struct AngularVelocity(pub f32);
+
+// turns toward the target, radians per second at most
+#[derive(Component, Clone, Copy)]
+pub struct Homing {
+    pub turn_rate: f32,
+}
+
+// flies on until stop, stands still until wait, then sets off at speed toward the target
+#[derive(Component, Clone)]
+pub struct DelayedAim {
+    pub stop: Timer,
+    pub wait: Timer,
+    pub speed: f32,
+}
+
+impl DelayedAim {
+    pub fn new(stop_seconds: f32, wait_seconds: f32, speed: f32) -> Self {
+        Self {
+            stop: Timer::from_seconds(stop_seconds, TimerMode::Once),
+            wait: Timer::from_seconds(wait_seconds, TimerMode::Once),
+            speed,
+        }
+    }
+}
+
 pub enum ShootType {
     Normal,
     Double,
     Triple,
 }
 
+impl ShootType {
+    pub fn damage(&self) -> f32 {
+        match self {
+            ShootType::Normal => SHOOT_DAMAGE_NORMAL,
+            ShootType::Double => SHOOT_DAMAGE_DOUBLE,
+            ShootType::Triple => SHOOT_DAMAGE_TRIPLE,
+        }
+    }
+}
+
 
 #[derive(Component)]
 pub struct Player {
@@ -75,6 +340,8 @@ pub struct Player {
     pub z: f32,
     pub is_enable: bool,
     pub shoot_type: ShootType,
+    // focus held, slower and with the hitbox shown
+    pub is_focus: bool,
 }
 
 impl Player {
@@ -86,15 +353,94 @@ impl Player {
     }
 }
 
+#[derive(Component)]
+pub struct PlayerHitboxDot;
+
 #[derive(Component)]
 pub struct PlayerStartPosition;
 
+#[derive(Clone, Copy, Debug)]
+pub enum HitboxShape {
+    Circle { radius: f32 },
+    // segment from -half_segment to half_segment, swept by radius
+    Capsule { half_segment: Vec2, radius: f32 },
+    // reserved for rectangular enemies and lasers
+    #[allow(dead_code)]
+    Aabb { half_size: Vec2 },
+}
+
+// collision shape, offset from the entity translation
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Hitbox {
+    pub shape: HitboxShape,
+    pub offset: Vec2,
+}
+
+impl Hitbox {
+    pub fn circle(radius: f32) -> Self {
+        Self { shape: HitboxShape::Circle { radius }, offset: Vec2::ZERO }
+    }
+    pub fn capsule(half_segment: Vec2, radius: f32) -> Self {
+        Self { shape: HitboxShape::Capsule { half_segment, radius }, offset: Vec2::ZERO }
+    }
+    // player shots are bolts twice as long as they are wide
+    pub fn player_shoot() -> Self {
+        Self::capsule(Vec2::new(0.0, SHOOT_RADIUS), SHOOT_RADIUS)
+    }
+}
+
 #[derive(Component)]
 pub struct Enemy {
     pub shoot_interval: Timer,
     pub point: f32,
+    // odds of leaving a power item behind
+    pub drop_chance: f64,
+}
+
+#[derive(Component)]
+pub struct Health {
+    pub current: f32,
+}
+
+impl Health {
+    pub fn new(current: f32) -> Self {
+        Self { current }
+    }
+}
+
+// taken off the target's health on a hit
+#[derive(Component)]
+pub struct Damage {
+    pub value: f32,
+}
+
+// shown white for a moment after taking a hit
+#[derive(Component)]
+pub struct HitFlash {
+    pub timer: Timer,
+}
+
+impl HitFlash {
+    pub fn new() -> Self {
+        Self { timer: Timer::from_seconds(HIT_FLASH_SECONDS, TimerMode::Once) }
+    }
 }
 
+// shots still hit but deal no damage, on the player nothing hits at all
+#[derive(Component)]
+pub struct Invulnerable {
+    pub timer: Timer,
+}
+
+impl Invulnerable {
+    pub fn new(seconds: f32) -> Self {
+        Self { timer: Timer::from_seconds(seconds, TimerMode::Once) }
+    }
+}
+
+#[derive(Component)]
+pub struct PowerItem;
+
 #[derive(Component)]
 pub struct AutoDespawn;
 
@@ -104,16 +450,159 @@ pub struct FromPlayerShoot;
 #[derive(Component)]
 pub struct FromEnemyShoot;
 
+// an enemy shot that already counted as a graze
 #[derive(Component)]
-pub struct ShootBang {
+pub struct Grazed;
+
+#[derive(Component)]
+pub struct GrazeSpark {
+    pub timer: Timer,
+}
+
+// points floating up from a kill
+#[derive(Component)]
+pub struct ScorePopup {
+    pub point: f32,
     pub timer: Timer,
 }
 
 #[derive(Component)]
-pub struct ShowBangPoint {
+pub struct ShowScorePoint {
     pub x: f32,
     pub y: f32,
+    pub point: f32,
 }
 
 #[derive(Component)]
-pub struct ScoreBoard;
\ No newline at end of file
+pub struct BombWave {
+    pub timer: Timer,
+}
+
+#[derive(Component)]
+pub struct ShowBombPoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Component)]
+pub struct ScoreBoard;
+
+#[derive(Component)]
+pub struct LivesBoard;
+
+#[derive(Component)]
+pub struct ContinueCountdown;
+
+#[derive(Component)]
+pub struct BossHealthBarFrame;
+
+#[derive(Component)]
+pub struct BossHealthBar;
+
+#[derive(Component)]
+pub struct OnTitleScreen;
+
+#[derive(Component)]
+pub struct OnKeyConfigScreen;
+
+#[derive(Component)]
+pub struct OnGameScreen;
+
+#[derive(Component)]
+pub struct OnPauseScreen;
+
+#[derive(Component)]
+pub struct OnGameOverScreen {
+    pub timer: Timer,
+    pub can_continue: bool,
+}
+
+#[derive(Component)]
+pub struct OnNameEntryScreen;
+
+#[derive(Component)]
+pub struct OnResultsScreen;
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn power_picks_the_shot() {
+        let mut player_status = PlayerStatus::default();
+        for (power, expected) in [(0, 0), (POWER_LEVELS[1] - 1, 0), (POWER_LEVELS[1], 1), (POWER_LEVELS[2] - 1, 1), (POWER_LEVELS[2], 2), (POWER_LEVELS[2] + 5, 2)] {
+            player_status.power = power;
+            let level = match player_status.shoot_type() {
+                ShootType::Normal => 0,
+                ShootType::Double => 1,
+                ShootType::Triple => 2,
+            };
+            assert_eq!(level, expected, "power {}", power);
+        }
+    }
+
+    #[test]
+    fn miss_loses_power() {
+        let mut player_status = PlayerStatus { power: POWER_LEVELS[2], ..PlayerStatus::default() };
+        player_status.miss();
+        assert_eq!(player_status.power, POWER_LEVELS[2] - POWER_LOSS_ON_MISS);
+
+        // never below nothing
+        player_status.power = 1;
+        player_status.miss();
+        assert_eq!(player_status.power, 0);
+    }
+
+    #[test]
+    fn chain_steps_up_to_the_cap() {
+        let mut player_status = PlayerStatus::default();
+        for _ in 1..CHAIN_KILLS_PER_STEP {
+            assert_eq!(player_status.kill(1.0), 1.0);
+        }
+        // the kill that completes a step already scores at the next one
+        assert_eq!(player_status.kill(1.0), 2.0);
+        assert_eq!(player_status.score, CHAIN_KILLS_PER_STEP as f32 + 1.0);
+
+        for _ in 0..CHAIN_KILLS_PER_STEP * CHAIN_MULTIPLIER_MAX * 2 {
+            player_status.kill(1.0);
+        }
+        assert_eq!(player_status.chain_multiplier(), CHAIN_MULTIPLIER_MAX);
+        assert_eq!(player_status.kill(1.0), CHAIN_MULTIPLIER_MAX as f32);
+    }
+
+    #[test]
+    fn lapsed_chain_drops_one_step() {
+        let mut player_status = PlayerStatus::default();
+        for _ in 0..CHAIN_KILLS_PER_STEP * 2 + 2 {
+            player_status.kill(1.0);
+        }
+        assert_eq!(player_status.chain_multiplier(), 3);
+
+        // a kill restarts the window
+        player_status.chain_timer.tick(Duration::from_secs_f32(CHAIN_WINDOW_SECONDS * 0.5));
+        player_status.kill(1.0);
+        assert_eq!(player_status.chain_timer.elapsed_secs(), 0.0);
+
+        // what player_chain_system does when the window runs out
+        assert!(player_status.chain_timer.tick(Duration::from_secs_f32(CHAIN_WINDOW_SECONDS)).just_finished());
+        player_status.chain_decay();
+        assert_eq!(player_status.chain_multiplier(), 2);
+        assert_eq!(player_status.chain_timer.elapsed_secs(), 0.0);
+        player_status.chain_decay();
+        assert_eq!(player_status.chain, 0);
+        assert_eq!(player_status.chain_multiplier(), 1);
+    }
+
+    #[test]
+    fn miss_resets_the_chain() {
+        let mut player_status = PlayerStatus::default();
+        for _ in 0..CHAIN_KILLS_PER_STEP * 3 {
+            player_status.kill(1.0);
+        }
+        player_status.miss();
+        assert_eq!(player_status.chain, 0);
+        assert_eq!(player_status.kill(1.0), 1.0);
+    }
+}
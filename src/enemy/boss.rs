@@ -0,0 +1,412 @@
+use bevy::prelude::*;
+
+use crate::{define::*, common::BulletPool, particle::{ParticleEvent, ParticleEmitter}, sound::SoundEvent, stage::{EnemyPatternKind, StageClearEvent}};
+
+use super::{EnemySpawnEvent, EnemyDefeatedEvent, emitter::{BulletPattern, aim_angle}};
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(FixedUpdate, (
+                boss_spawn_system,
+                boss_phase_system,
+                boss_move_system,
+                boss_attack_system,
+            ).chain().in_set(EnemyPatternKind::Boss).run_if(in_state(GameState::InGame)))
+            .add_systems(Update, boss_health_bar_system.run_if(in_state(GameState::InGame)));
+    }
+}
+
+#[derive(Clone, Copy)]
+enum BossMovement {
+    // side to side across the top
+    Sway,
+    // small loop around home
+    Circle,
+    // stays at home
+    Hold,
+}
+
+#[derive(Clone, Copy)]
+enum BossAttack {
+    // fan of three at the player
+    Aimed,
+    // full circle of bullets, turning a little every volley
+    TurningRing,
+    // two opposite streams turning fast
+    Spiral,
+}
+
+impl BossAttack {
+    fn interval(self) -> f32 {
+        match self {
+            BossAttack::Aimed => 0.8,
+            BossAttack::TurningRing => 1.0,
+            BossAttack::Spiral => 0.08,
+        }
+    }
+
+    fn pattern(self) -> BulletPattern {
+        match self {
+            BossAttack::Aimed => BulletPattern::Spread { count: 3, spread: 0.52 },
+            BossAttack::TurningRing => BulletPattern::Spiral { arms: 16, turn: 0.2 },
+            BossAttack::Spiral => BulletPattern::Spiral { arms: 2, turn: 0.3 },
+        }
+    }
+}
+
+struct BossPhase {
+    // times the health from the stage timeline
+    health_scale: f32,
+    seconds: f32,
+    // bonus for breaking the phase before time runs out
+    point: f32,
+    movement: BossMovement,
+    attack: BossAttack,
+}
+
+const BOSS_PHASES: [BossPhase; 3] = [
+    BossPhase { health_scale: 1.0, seconds: 30.0, point: 10.0, movement: BossMovement::Sway, attack: BossAttack::Aimed },
+    BossPhase { health_scale: 1.5, seconds: 30.0, point: 20.0, movement: BossMovement::Circle, attack: BossAttack::TurningRing },
+    BossPhase { health_scale: 2.0, seconds: 40.0, point: 30.0, movement: BossMovement::Hold, attack: BossAttack::Spiral },
+];
+
+#[derive(Component)]
+pub struct Boss {
+    phase: usize,
+    base_health: f32,
+    phase_health: f32,
+    phase_timer: Timer,
+    attack_timer: Timer,
+    home: Vec2,
+    // where the transition back home starts
+    from: Vec2,
+    angle: f32,
+}
+
+impl Boss {
+    fn start_phase(&mut self, health: &mut Health, translation: Vec3) -> Invulnerable {
+        let phase = &BOSS_PHASES[self.phase];
+        self.phase_health = self.base_health * phase.health_scale;
+        self.phase_timer = Timer::from_seconds(phase.seconds, TimerMode::Once);
+        self.attack_timer = Timer::from_seconds(phase.attack.interval(), TimerMode::Repeating);
+        self.from = translation.truncate();
+        self.angle = 0.0;
+        health.current = self.phase_health;
+
+        Invulnerable::new(BOSS_TRANSITION_SECONDS)
+    }
+}
+
+fn boss_spawn_system(
+    mut commands: Commands,
+    mut spawn_events: EventReader<EnemySpawnEvent>,
+) {
+    for event in EnemySpawnEvent::read(&mut spawn_events, EnemyPatternKind::Boss) {
+        let (x, y) = (event.x, event.y);
+
+        // flies in from above the playfield during the first transition
+        let translation = Vec3::new(x, y + 200.0, 9.0);
+        let mut health = Health::new(event.health);
+        let mut boss = Boss {
+            phase: 0,
+            base_health: event.health,
+            phase_health: event.health,
+            phase_timer: Timer::default(),
+            attack_timer: Timer::default(),
+            home: Vec2::new(x, y),
+            from: Vec2::ZERO,
+            angle: 0.0,
+        };
+        let invulnerable = boss.start_phase(&mut health, translation);
+
+        // no BulletEmitter, boss_attack_system fires for the boss
+        commands.spawn((
+            SpatialBundle::from_transform(Transform::from_translation(translation)),
+            Enemy {
+                shoot_interval: Timer::default(),
+                point: event.point,
+                drop_chance: event.drop_chance,
+            },
+            health,
+            Hitbox::circle(BOSS_RADIUS),
+            Velocity {x: 0.0, y: 0.0},
+            boss,
+            invulnerable,
+            OnGameScreen,
+        ));
+
+        // hp bar
+        commands.spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(40.0),
+                    left: Val::Percent(5.0),
+                    width: Val::Percent(90.0),
+                    height: Val::Px(6.0),
+                    ..default()
+                },
+                background_color: Color::rgb(0.3, 0.3, 0.3).into(),
+                ..default()
+            },
+            BossHealthBarFrame,
+            OnGameScreen,
+        )).with_children(|p| {
+            p.spawn((
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    background_color: Color::RED.into(),
+                    ..default()
+                },
+                BossHealthBar,
+            ));
+        });
+    }
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn boss_phase_system(
+    mut commands: Commands,
+    mut bullet_pool: ResMut<BulletPool>,
+    mut query: Query<(Entity, &Transform, &Enemy, &mut Boss, &mut Health, Option<&mut Invulnerable>)>,
+    enemy_shoots: Query<(Entity, &Transform), With<FromEnemyShoot>>,
+    mut player_status: ResMut<PlayerStatus>,
+    mut stage_clear_events: EventWriter<StageClearEvent>,
+    mut defeated_events: EventWriter<EnemyDefeatedEvent>,
+    mut particle_events: EventWriter<ParticleEvent>,
+    time: Res<Time>,
+) {
+    for (entity, transform, enemy, mut boss, mut health, invulnerable) in query.iter_mut() {
+        if let Some(mut invulnerable) = invulnerable {
+            if invulnerable.timer.tick(time.delta()).just_finished() {
+                commands.entity(entity).remove::<Invulnerable>();
+            }
+            continue;
+        }
+
+        let is_broken = health.current <= 0.0;
+        if !is_broken && !boss.phase_timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+
+        if is_broken {
+            player_status.score += BOSS_PHASES[boss.phase].point;
+        }
+
+        // every phase change clears the screen
+        for (enemy_shoot, enemy_shoot_transform) in enemy_shoots.iter() {
+            bullet_pool.release(&mut commands, enemy_shoot);
+            particle_events.send(ParticleEvent { position: enemy_shoot_transform.translation.truncate(), emitter: ParticleEmitter::BULLET_CANCEL });
+        }
+
+        if boss.phase + 1 < BOSS_PHASES.len() {
+            boss.phase += 1;
+            let invulnerable = boss.start_phase(&mut health, transform.translation);
+            commands.entity(entity).insert(invulnerable);
+            continue;
+        }
+
+        // defeated, or gone when the last phase runs out
+        commands.entity(entity).despawn();
+        if is_broken {
+            let point = player_status.kill(enemy.point);
+
+            let x = transform.translation.x;
+            let y = transform.translation.y;
+            particle_events.send(ParticleEvent { position: Vec2::new(x, y), emitter: ParticleEmitter::ENEMY_DEFEATED });
+            commands.spawn((ShowScorePoint {x, y, point}, OnGameScreen));
+            defeated_events.send(EnemyDefeatedEvent { x, y, drop_chance: enemy.drop_chance });
+        }
+        stage_clear_events.send(StageClearEvent);
+    }
+}
+
+fn boss_move_system(
+    mut query: Query<(&mut Transform, &Boss, Option<&Invulnerable>)>,
+) {
+    for (mut transform, boss, invulnerable) in query.iter_mut() {
+        let position = match invulnerable {
+            Some(invulnerable) => boss.from.lerp(boss.home, invulnerable.timer.percent()),
+            None => {
+                // every routine starts at home
+                let t = boss.phase_timer.elapsed_secs();
+                match BOSS_PHASES[boss.phase].movement {
+                    BossMovement::Sway => boss.home + Vec2::new((t * 0.8).sin() * 120.0, 0.0),
+                    BossMovement::Circle => boss.home + Vec2::new((t * 1.5).sin() * 80.0, ((t * 1.5).cos() - 1.0) * 40.0),
+                    BossMovement::Hold => boss.home,
+                }
+            }
+        };
+
+        transform.translation = position.extend(transform.translation.z);
+    }
+}
+
+fn boss_attack_system(
+    mut commands: Commands,
+    mut bullet_pool: ResMut<BulletPool>,
+    mut query: Query<(&Transform, &mut Boss), Without<Invulnerable>>,
+    player: Query<&Transform, With<Player>>,
+    speed_control: Res<SpeedControl>,
+    time: Res<Time>,
+    mut sound_events: EventWriter<SoundEvent>,
+) {
+    for (transform, mut boss) in query.iter_mut() {
+        if !boss.attack_timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        sound_events.send(SoundEvent::EnemyShoot);
+
+        let origin = transform.translation.truncate();
+        let speed = SHOOT_VELOCITY * speed_control.value;
+
+        let target = player.get_single().ok().map(|player| player.translation.truncate());
+        let aim = aim_angle(origin, target);
+        let angles = BOSS_PHASES[boss.phase].attack.pattern().angles(aim, &mut boss.angle);
+
+        for angle in angles {
+            let direction = Vec2::from_angle(angle) * speed;
+            bullet_pool.spawn(&mut commands, origin.extend(0.0), (
+                Velocity { x: direction.x, y: direction.y },
+                Hitbox::circle(SHOOT_RADIUS),
+                FromEnemyShoot,
+            ));
+        }
+    }
+}
+
+fn boss_health_bar_system(
+    mut commands: Commands,
+    boss: Query<(&Boss, &Health)>,
+    mut bars: Query<&mut Style, With<BossHealthBar>>,
+    frames: Query<Entity, With<BossHealthBarFrame>>,
+) {
+    match boss.get_single() {
+        Ok((boss, health)) => {
+            let percent = (health.current / boss.phase_health).clamp(0.0, 1.0) * 100.0;
+            for mut style in bars.iter_mut() {
+                style.width = Val::Percent(percent);
+            }
+        }
+        Err(_) => {
+            for frame in frames.iter() {
+                commands.entity(frame).despawn_recursive();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{headless_app, enemy::EnemySchedule, replay::ReplayMode, rng::GameSeed};
+
+    const BOSS_HEALTH: f32 = 40.0;
+    const BOSS_POINT: f32 = 50.0;
+    // all three phases run out plus their transitions, with room to spare
+    const BOSS_FRAMES: usize = 60 * 150;
+
+    // the boss alone on the playfield, the player out of reach of its shots
+    fn boss_app() -> App {
+        let mut app = headless_app(Vec2::new(WINDOW_SIZE_WIDTH, WINDOW_SIZE_HEIGHT), GameSeed(1), ReplayMode::Off);
+        app.finish();
+        app.cleanup();
+        while *app.world.resource::<State<GameState>>().get() != GameState::InGame {
+            app.update();
+        }
+        app.world.resource_mut::<EnemySchedule>().cursor = usize::MAX;
+        app.world.send_event(EnemySpawnEvent {
+            pattern: EnemyPatternKind::Boss,
+            x: 0.0,
+            y: 220.0,
+            velocity: (0.0, 0.0),
+            point: BOSS_POINT,
+            health: BOSS_HEALTH,
+            drop_chance: 0.0,
+            shoot_duration: 1.0,
+            script: None,
+        });
+        app
+    }
+
+    fn step(app: &mut App) {
+        let players: Vec<Entity> = app.world.query_filtered::<Entity, (With<Player>, Without<Invulnerable>)>().iter(&app.world).collect();
+        for player in players {
+            app.world.entity_mut(player).insert(Invulnerable::new(3600.0));
+        }
+        app.update();
+    }
+
+    // the phase the boss is in and whether it is in a transition
+    fn boss(app: &mut App) -> Option<(usize, bool)> {
+        app.world.query::<(&Boss, Has<Invulnerable>)>().iter(&app.world).next().map(|(boss, invulnerable)| (boss.phase, invulnerable))
+    }
+
+    fn is_cleared(app: &App) -> bool {
+        *app.world.resource::<State<GameState>>().get() == GameState::Results
+    }
+
+    #[test]
+    fn drained_boss_goes_through_every_phase() {
+        let mut app = boss_app();
+        let mut phases = Vec::new();
+        let mut transition_frames = 0;
+        for _ in 0..BOSS_FRAMES {
+            step(&mut app);
+            if is_cleared(&app) {
+                break;
+            }
+            let Some((phase, invulnerable)) = boss(&mut app) else {
+                continue;
+            };
+            if phases.last() != Some(&phase) {
+                phases.push(phase);
+            }
+            let mut health = app.world.query_filtered::<&mut Health, With<Boss>>().single_mut(&mut app.world);
+            if invulnerable {
+                transition_frames += 1;
+                // damage in a transition does not break the phase
+                assert_eq!(health.current, BOSS_HEALTH * BOSS_PHASES[phase].health_scale);
+            } else {
+                health.current = 0.0;
+            }
+        }
+
+        assert!(is_cleared(&app));
+        assert_eq!(phases, vec![0, 1, 2]);
+        assert!(boss(&mut app).is_none());
+        // each transition holds for BOSS_TRANSITION_SECONDS, an update advances the game less than two frames
+        assert!(transition_frames >= 3 * (BOSS_TRANSITION_SECONDS / (HEADLESS_FRAME_SECONDS * 2.0)) as usize);
+        let bonus: f32 = BOSS_PHASES.iter().map(|phase| phase.point).sum();
+        assert!(app.world.resource::<PlayerStatus>().score >= bonus + BOSS_POINT);
+    }
+
+    #[test]
+    fn boss_leaves_when_time_runs_out() {
+        let mut app = boss_app();
+        let mut phases = Vec::new();
+        for _ in 0..BOSS_FRAMES {
+            step(&mut app);
+            if is_cleared(&app) {
+                break;
+            }
+            if let Some((phase, _)) = boss(&mut app) {
+                if phases.last() != Some(&phase) {
+                    phases.push(phase);
+                }
+            }
+        }
+
+        assert!(is_cleared(&app));
+        assert_eq!(phases, vec![0, 1, 2]);
+        // no phase bonus or kill without breaking it
+        assert_eq!(app.world.resource::<PlayerStatus>().score, 0.0);
+    }
+}
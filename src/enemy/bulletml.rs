@@ -0,0 +1,971 @@
+use bevy::{
+    asset::{AssetLoader, AsyncReadExt, LoadContext, io::Reader},
+    prelude::*,
+    reflect::TypePath,
+    utils::{BoxedFuture, HashMap},
+};
+use rand::Rng;
+use thiserror::Error;
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::{define::*, common::BulletPool, rng::{GameRng, RngStream}, sound::SoundEvent};
+
+use super::emitter::aim_angle;
+
+// BulletML scripts, see http://www.asahi-net.or.jp/~cs8k-cyu/bulletml/
+// the script runs one frame per gameplay tick. angles are BulletML degrees, 0 up and
+// clockwise, speeds are units per frame. only the vertical type is supported
+
+#[derive(Debug, Error)]
+pub enum BulletMlError {
+    #[error("could not read bullet script: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse bullet script: {0}")]
+    Xml(#[from] xml::reader::Error),
+    #[error("invalid bullet script: {0}")]
+    Invalid(String),
+}
+
+fn invalid(message: impl Into<String>) -> BulletMlError {
+    BulletMlError::Invalid(message.into())
+}
+
+#[derive(Debug)]
+enum Expr {
+    Number(f32),
+    Rank,
+    Rand,
+    // $1 is the first
+    Param(usize),
+    Neg(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token {
+    Number(f32),
+    Rank,
+    Rand,
+    Param(usize),
+    Symbol(char),
+}
+
+impl Expr {
+    fn parse(text: &str) -> Result<Expr, BulletMlError> {
+        let tokens = tokenize(text)?;
+        let mut cursor = 0;
+        let expr = parse_sum(&tokens, &mut cursor)?;
+        if cursor != tokens.len() {
+            return Err(invalid(format!("unexpected token in \"{}\"", text)));
+        }
+        Ok(expr)
+    }
+
+    fn eval(&self, params: &[f32], context: &mut Context) -> f32 {
+        match self {
+            Expr::Number(value) => *value,
+            Expr::Rank => context.rank,
+            Expr::Rand => (context.rand)(),
+            // a missing param reads as 0
+            Expr::Param(index) => params.get(*index).copied().unwrap_or(0.0),
+            Expr::Neg(expr) => -expr.eval(params, context),
+            Expr::Binary(symbol, left, right) => {
+                let (left, right) = (left.eval(params, context), right.eval(params, context));
+                match symbol {
+                    '+' => left + right,
+                    '-' => left - right,
+                    '*' => left * right,
+                    '/' => left / right,
+                    _ => left % right,
+                }
+            }
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, BulletMlError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut number = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit() || **c == '.') {
+                number.push(c);
+                chars.next();
+            }
+            let value = number.parse().map_err(|_| invalid(format!("bad number \"{}\"", number)))?;
+            tokens.push(Token::Number(value));
+        } else if c == '$' {
+            chars.next();
+            let mut name = String::new();
+            while let Some(&c) = chars.peek().filter(|c| c.is_ascii_alphanumeric()) {
+                name.push(c);
+                chars.next();
+            }
+            tokens.push(match name.as_str() {
+                "rank" => Token::Rank,
+                "rand" => Token::Rand,
+                _ => match name.parse::<usize>() {
+                    Ok(index) if index >= 1 => Token::Param(index - 1),
+                    _ => return Err(invalid(format!("unknown variable ${}", name))),
+                },
+            });
+        } else if "+-*/%()".contains(c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            return Err(invalid(format!("unexpected '{}' in \"{}\"", c, text)));
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_sum(tokens: &[Token], cursor: &mut usize) -> Result<Expr, BulletMlError> {
+    let mut expr = parse_product(tokens, cursor)?;
+    while let Some(Token::Symbol(symbol @ ('+' | '-'))) = tokens.get(*cursor).copied() {
+        *cursor += 1;
+        expr = Expr::Binary(symbol, Box::new(expr), Box::new(parse_product(tokens, cursor)?));
+    }
+    Ok(expr)
+}
+
+fn parse_product(tokens: &[Token], cursor: &mut usize) -> Result<Expr, BulletMlError> {
+    let mut expr = parse_unary(tokens, cursor)?;
+    while let Some(Token::Symbol(symbol @ ('*' | '/' | '%'))) = tokens.get(*cursor).copied() {
+        *cursor += 1;
+        expr = Expr::Binary(symbol, Box::new(expr), Box::new(parse_unary(tokens, cursor)?));
+    }
+    Ok(expr)
+}
+
+fn parse_unary(tokens: &[Token], cursor: &mut usize) -> Result<Expr, BulletMlError> {
+    let token = tokens.get(*cursor).copied();
+    *cursor += 1;
+    match token {
+        Some(Token::Symbol('-')) => Ok(Expr::Neg(Box::new(parse_unary(tokens, cursor)?))),
+        Some(Token::Symbol('+')) => parse_unary(tokens, cursor),
+        Some(Token::Symbol('(')) => {
+            let expr = parse_sum(tokens, cursor)?;
+            if tokens.get(*cursor) != Some(&Token::Symbol(')')) {
+                return Err(invalid("missing ')'"));
+            }
+            *cursor += 1;
+            Ok(expr)
+        }
+        Some(Token::Number(value)) => Ok(Expr::Number(value)),
+        Some(Token::Rank) => Ok(Expr::Rank),
+        Some(Token::Rand) => Ok(Expr::Rand),
+        Some(Token::Param(index)) => Ok(Expr::Param(index)),
+        _ => Err(invalid("expression ends too early")),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DirectionKind {
+    Aim,
+    Absolute,
+    Relative,
+    Sequence,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SpeedKind {
+    Absolute,
+    Relative,
+    Sequence,
+}
+
+#[derive(Debug)]
+struct Direction {
+    kind: DirectionKind,
+    value: Expr,
+}
+
+#[derive(Debug)]
+struct Speed {
+    kind: SpeedKind,
+    value: Expr,
+}
+
+// an inline element keeps its caller's params, a reference brings its own
+#[derive(Debug, Default)]
+struct Call {
+    index: usize,
+    params: Option<Vec<Expr>>,
+}
+
+#[derive(Debug)]
+enum Step {
+    Repeat { times: Expr, action: Call },
+    Fire(Call),
+    ChangeSpeed { speed: Speed, term: Expr },
+    ChangeDirection { direction: Direction, term: Expr },
+    Accel { horizontal: Option<Speed>, vertical: Option<Speed>, term: Expr },
+    Wait(Expr),
+    Vanish,
+    Action(Call),
+}
+
+#[derive(Debug, Default)]
+struct Action {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Default)]
+struct Fire {
+    direction: Option<Direction>,
+    speed: Option<Speed>,
+    bullet: Call,
+}
+
+#[derive(Debug, Default)]
+struct Bullet {
+    direction: Option<Direction>,
+    speed: Option<Speed>,
+    actions: Vec<Call>,
+}
+
+// a whole script, every action, fire and bullet kept in one list each
+#[derive(Asset, TypePath, Debug, Default)]
+pub struct BulletMl {
+    actions: Vec<Action>,
+    fires: Vec<Fire>,
+    bullets: Vec<Bullet>,
+    // the actions labelled top, top1, top2... run side by side
+    top: Vec<usize>,
+}
+
+struct Element {
+    name: String,
+    attributes: HashMap<String, String>,
+    text: String,
+    children: Vec<Element>,
+}
+
+impl Element {
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn expr(&self, name: &str) -> Result<Expr, BulletMlError> {
+        let child = self.child(name).ok_or_else(|| invalid(format!("<{}> needs <{}>", self.name, name)))?;
+        Expr::parse(&child.text)
+    }
+
+    fn label(&self) -> Option<&str> {
+        self.attributes.get("label").map(|label| label.as_str())
+    }
+}
+
+fn parse_document(bytes: &[u8]) -> Result<Element, BulletMlError> {
+    let mut stack: Vec<Element> = Vec::new();
+    for event in EventReader::new(bytes) {
+        match event? {
+            XmlEvent::StartElement { name, attributes, .. } => stack.push(Element {
+                name: name.local_name,
+                attributes: attributes.into_iter().map(|attribute| (attribute.name.local_name, attribute.value)).collect(),
+                text: String::new(),
+                children: Vec::new(),
+            }),
+            XmlEvent::EndElement { .. } => {
+                let element = stack.pop().ok_or_else(|| invalid("unbalanced elements"))?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            XmlEvent::Characters(text) | XmlEvent::CData(text) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&text);
+                }
+            }
+            _ => {}
+        }
+    }
+    Err(invalid("no root element"))
+}
+
+#[derive(Default)]
+struct Compiler {
+    script: BulletMl,
+    // labelled definitions get their slots up front so references can point forward
+    labels: HashMap<(String, String), usize>,
+}
+
+impl Compiler {
+    fn reserve(&mut self, element: &Element) {
+        if let Some(label) = element.label() {
+            let index = match element.name.as_str() {
+                "action" => { self.script.actions.push(Action::default()); self.script.actions.len() - 1 }
+                "fire" => { self.script.fires.push(Fire::default()); self.script.fires.len() - 1 }
+                "bullet" => { self.script.bullets.push(Bullet::default()); self.script.bullets.len() - 1 }
+                _ => return,
+            };
+            self.labels.insert((element.name.clone(), label.to_string()), index);
+        }
+        for child in element.children.iter() {
+            self.reserve(child);
+        }
+    }
+
+    fn reference(&self, kind: &str, element: &Element) -> Result<Call, BulletMlError> {
+        let label = element.label().ok_or_else(|| invalid(format!("<{}> needs a label", element.name)))?;
+        let index = *self.labels.get(&(kind.to_string(), label.to_string()))
+            .ok_or_else(|| invalid(format!("no {} labelled \"{}\"", kind, label)))?;
+        let params = element.children.iter()
+            .filter(|child| child.name == "param")
+            .map(|child| Expr::parse(&child.text))
+            .collect::<Result<_, _>>()?;
+        Ok(Call { index, params: Some(params) })
+    }
+
+    // an inline definition, or a reference when the element is the Ref form
+    fn call(&mut self, kind: &str, element: &Element) -> Result<Call, BulletMlError> {
+        if element.name == kind {
+            let index = match kind {
+                "action" => self.action(element)?,
+                "fire" => self.fire(element)?,
+                _ => self.bullet(element)?,
+            };
+            Ok(Call { index, params: None })
+        } else {
+            self.reference(kind, element)
+        }
+    }
+
+    fn find_call(&mut self, kind: &str, element: &Element) -> Result<Call, BulletMlError> {
+        let reference = format!("{}Ref", kind);
+        let child = element.children.iter()
+            .find(|child| child.name == kind || child.name == reference)
+            .ok_or_else(|| invalid(format!("<{}> needs <{}> or <{}>", element.name, kind, reference)))?;
+        self.call(kind, child)
+    }
+
+    fn slot(&self, kind: &str, element: &Element) -> Option<usize> {
+        element.label().and_then(|label| self.labels.get(&(kind.to_string(), label.to_string())).copied())
+    }
+
+    fn action(&mut self, element: &Element) -> Result<usize, BulletMlError> {
+        let mut steps = Vec::new();
+        for child in element.children.iter() {
+            steps.push(match child.name.as_str() {
+                "repeat" => Step::Repeat { times: child.expr("times")?, action: self.find_call("action", child)? },
+                "fire" | "fireRef" => Step::Fire(self.call("fire", child)?),
+                "changeSpeed" => Step::ChangeSpeed { speed: speed(child.child("speed"))?.ok_or_else(|| invalid("<changeSpeed> needs <speed>"))?, term: child.expr("term")? },
+                "changeDirection" => Step::ChangeDirection { direction: direction(child.child("direction"))?.ok_or_else(|| invalid("<changeDirection> needs <direction>"))?, term: child.expr("term")? },
+                "accel" => Step::Accel { horizontal: speed(child.child("horizontal"))?, vertical: speed(child.child("vertical"))?, term: child.expr("term")? },
+                "wait" => Step::Wait(Expr::parse(&child.text)?),
+                "vanish" => Step::Vanish,
+                "action" | "actionRef" => Step::Action(self.call("action", child)?),
+                _ => continue,
+            });
+        }
+        let action = Action { steps };
+        Ok(match self.slot("action", element) {
+            Some(index) => { self.script.actions[index] = action; index }
+            None => { self.script.actions.push(action); self.script.actions.len() - 1 }
+        })
+    }
+
+    fn fire(&mut self, element: &Element) -> Result<usize, BulletMlError> {
+        let fire = Fire {
+            direction: direction(element.child("direction"))?,
+            speed: speed(element.child("speed"))?,
+            bullet: self.find_call("bullet", element)?,
+        };
+        Ok(match self.slot("fire", element) {
+            Some(index) => { self.script.fires[index] = fire; index }
+            None => { self.script.fires.push(fire); self.script.fires.len() - 1 }
+        })
+    }
+
+    fn bullet(&mut self, element: &Element) -> Result<usize, BulletMlError> {
+        let mut actions = Vec::new();
+        for child in element.children.iter().filter(|child| child.name == "action" || child.name == "actionRef") {
+            actions.push(self.call("action", child)?);
+        }
+        let bullet = Bullet {
+            direction: direction(element.child("direction"))?,
+            speed: speed(element.child("speed"))?,
+            actions,
+        };
+        Ok(match self.slot("bullet", element) {
+            Some(index) => { self.script.bullets[index] = bullet; index }
+            None => { self.script.bullets.push(bullet); self.script.bullets.len() - 1 }
+        })
+    }
+}
+
+fn direction(element: Option<&Element>) -> Result<Option<Direction>, BulletMlError> {
+    let Some(element) = element else {
+        return Ok(None);
+    };
+    let kind = match element.attributes.get("type").map(|kind| kind.as_str()) {
+        None | Some("aim") => DirectionKind::Aim,
+        Some("absolute") => DirectionKind::Absolute,
+        Some("relative") => DirectionKind::Relative,
+        Some("sequence") => DirectionKind::Sequence,
+        Some(kind) => return Err(invalid(format!("unknown direction type \"{}\"", kind))),
+    };
+    Ok(Some(Direction { kind, value: Expr::parse(&element.text)? }))
+}
+
+fn speed(element: Option<&Element>) -> Result<Option<Speed>, BulletMlError> {
+    let Some(element) = element else {
+        return Ok(None);
+    };
+    let kind = match element.attributes.get("type").map(|kind| kind.as_str()) {
+        None | Some("absolute") => SpeedKind::Absolute,
+        Some("relative") => SpeedKind::Relative,
+        Some("sequence") => SpeedKind::Sequence,
+        Some(kind) => return Err(invalid(format!("unknown speed type \"{}\"", kind))),
+    };
+    Ok(Some(Speed { kind, value: Expr::parse(&element.text)? }))
+}
+
+impl BulletMl {
+    pub fn parse(bytes: &[u8]) -> Result<BulletMl, BulletMlError> {
+        let root = parse_document(bytes)?;
+        if root.name != "bulletml" {
+            return Err(invalid(format!("root is <{}>, not <bulletml>", root.name)));
+        }
+        // angles are run as a vertical shooter's, a horizontal script would come out turned a quarter
+        match root.attributes.get("type").map(|kind| kind.as_str()) {
+            None | Some("none") | Some("vertical") => {}
+            Some(kind) => return Err(invalid(format!("unsupported bulletml type \"{}\"", kind))),
+        }
+
+        let mut compiler = Compiler::default();
+        compiler.reserve(&root);
+        for child in root.children.iter() {
+            match child.name.as_str() {
+                "action" => { compiler.action(child)?; }
+                "fire" => { compiler.fire(child)?; }
+                "bullet" => { compiler.bullet(child)?; }
+                _ => {}
+            }
+        }
+
+        let mut top: Vec<(&str, usize)> = root.children.iter()
+            .filter(|child| child.name == "action")
+            .filter_map(|child| child.label().filter(|label| label.starts_with("top")).zip(compiler.slot("action", child)))
+            .collect();
+        if top.is_empty() {
+            return Err(invalid("no action labelled top"));
+        }
+        top.sort();
+        compiler.script.top = top.into_iter().map(|(_, index)| index).collect();
+
+        Ok(compiler.script)
+    }
+}
+
+#[derive(Default)]
+pub struct BulletMlLoader;
+
+impl AssetLoader for BulletMlLoader {
+    type Asset = BulletMl;
+    type Settings = ();
+    type Error = BulletMlError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<BulletMl, BulletMlError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            BulletMl::parse(&bytes)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["xml"]
+    }
+}
+
+// what a script can see while it runs a frame
+pub struct Context<'a> {
+    pub position: Vec2,
+    pub target: Option<Vec2>,
+    pub rank: f32,
+    // 0.0 to 1.0, from the enemy fire stream in game
+    pub rand: &'a mut dyn FnMut() -> f32,
+}
+
+impl Context<'_> {
+    fn aim(&self) -> f32 {
+        90.0 - aim_angle(self.position, self.target).to_degrees()
+    }
+}
+
+// a bullet fired this frame, velocity in units per frame
+pub struct Shot {
+    pub velocity: Vec2,
+    // only for bullets with actions of their own
+    pub runner: Option<BulletMlRunner>,
+}
+
+struct Frame {
+    action: usize,
+    step: usize,
+    params: Vec<f32>,
+    // times the action still has to run, counting this one
+    left: u32,
+}
+
+struct Thread {
+    stack: Vec<Frame>,
+    wait: u32,
+}
+
+impl Thread {
+    fn new(action: usize, params: Vec<f32>) -> Self {
+        Self {
+            stack: vec![Frame { action, step: 0, params, left: 1 }],
+            wait: 0,
+        }
+    }
+}
+
+// changes value by step every frame for left frames
+struct Change {
+    step: f32,
+    left: u32,
+}
+
+fn apply_change(change: &mut Option<Change>, value: &mut f32) {
+    if let Some(running) = change {
+        *value += running.step;
+        running.left -= 1;
+        if running.left == 0 {
+            *change = None;
+        }
+    }
+}
+
+fn wrap_degrees(degrees: f32) -> f32 {
+    (degrees + 180.0).rem_euclid(360.0) - 180.0
+}
+
+fn call_params(call: &Call, params: &[f32], context: &mut Context) -> Vec<f32> {
+    match &call.params {
+        Some(exprs) => exprs.iter().map(|expr| expr.eval(params, context)).collect(),
+        None => params.to_vec(),
+    }
+}
+
+// runs a script for an enemy, which starts the top actions, or for a bullet it fired,
+// which runs the bullet's own actions and steers it
+#[derive(Component)]
+pub struct BulletMlRunner {
+    pub script: Handle<BulletMl>,
+    threads: Vec<Thread>,
+    is_started: bool,
+    is_vanished: bool,
+    direction: f32,
+    speed: f32,
+    // extra velocity from accel, y is down the screen as in BulletML
+    accel: Vec2,
+    change_direction: Option<Change>,
+    change_speed: Option<Change>,
+    change_horizontal: Option<Change>,
+    change_vertical: Option<Change>,
+    // direction and speed of the last shot, for sequence
+    last_direction: f32,
+    last_speed: f32,
+}
+
+impl BulletMlRunner {
+    pub fn new(script: Handle<BulletMl>) -> Self {
+        Self {
+            script,
+            threads: Vec::new(),
+            is_started: false,
+            is_vanished: false,
+            direction: 180.0,
+            speed: 0.0,
+            accel: Vec2::ZERO,
+            change_direction: None,
+            change_speed: None,
+            change_horizontal: None,
+            change_vertical: None,
+            last_direction: 0.0,
+            last_speed: 1.0,
+        }
+    }
+
+    pub fn is_vanished(&self) -> bool {
+        self.is_vanished
+    }
+
+    // units per frame, in game axes
+    pub fn velocity(&self) -> Vec2 {
+        Vec2::from_angle((90.0 - self.direction).to_radians()) * self.speed + Vec2::new(self.accel.x, -self.accel.y)
+    }
+
+    // one frame: runs the actions, then moves the changes along
+    pub fn step(&mut self, script: &BulletMl, context: &mut Context) -> Vec<Shot> {
+        let mut shots = Vec::new();
+        if self.is_vanished {
+            return shots;
+        }
+        if !self.is_started {
+            self.is_started = true;
+            self.threads = script.top.iter().map(|&action| Thread::new(action, Vec::new())).collect();
+        }
+
+        let mut threads = std::mem::take(&mut self.threads);
+        for thread in threads.iter_mut() {
+            self.run(thread, script, context, &mut shots);
+        }
+        threads.retain(|thread| !thread.stack.is_empty());
+        self.threads = threads;
+
+        apply_change(&mut self.change_direction, &mut self.direction);
+        apply_change(&mut self.change_speed, &mut self.speed);
+        apply_change(&mut self.change_horizontal, &mut self.accel.x);
+        apply_change(&mut self.change_vertical, &mut self.accel.y);
+        shots
+    }
+
+    fn run(&mut self, thread: &mut Thread, script: &BulletMl, context: &mut Context, shots: &mut Vec<Shot>) {
+        if thread.wait > 0 {
+            thread.wait -= 1;
+            if thread.wait > 0 {
+                return;
+            }
+        }
+
+        // a repeat without a wait in it still has to end the frame
+        for _ in 0..BULLETML_STEPS_PER_FRAME {
+            let Some(frame) = thread.stack.last_mut() else {
+                return;
+            };
+            let Some(step) = script.actions[frame.action].steps.get(frame.step) else {
+                frame.left -= 1;
+                if frame.left > 0 {
+                    frame.step = 0;
+                } else {
+                    thread.stack.pop();
+                }
+                continue;
+            };
+            frame.step += 1;
+            let params = frame.params.clone();
+
+            match step {
+                Step::Repeat { times, action } => {
+                    let times = times.eval(&params, context).floor();
+                    if times >= 1.0 {
+                        let params = call_params(action, &params, context);
+                        thread.stack.push(Frame { action: action.index, step: 0, params, left: times as u32 });
+                    }
+                }
+                Step::Action(action) => {
+                    let params = call_params(action, &params, context);
+                    thread.stack.push(Frame { action: action.index, step: 0, params, left: 1 });
+                }
+                Step::Fire(fire) => shots.push(self.fire(script, fire, &params, context)),
+                Step::ChangeSpeed { speed, term } => {
+                    let term = term.eval(&params, context).max(1.0) as u32;
+                    let value = speed.value.eval(&params, context);
+                    self.change_speed = Some(change(speed.kind, self.speed, value, term));
+                }
+                Step::ChangeDirection { direction, term } => {
+                    let term = term.eval(&params, context).max(1.0) as u32;
+                    let value = direction.value.eval(&params, context);
+                    let step = match direction.kind {
+                        DirectionKind::Sequence => value,
+                        DirectionKind::Aim => wrap_degrees(context.aim() + value - self.direction) / term as f32,
+                        DirectionKind::Absolute => wrap_degrees(value - self.direction) / term as f32,
+                        DirectionKind::Relative => wrap_degrees(value) / term as f32,
+                    };
+                    self.change_direction = Some(Change { step, left: term });
+                }
+                Step::Accel { horizontal, vertical, term } => {
+                    let term = term.eval(&params, context).max(1.0) as u32;
+                    if let Some(horizontal) = horizontal {
+                        let value = horizontal.value.eval(&params, context);
+                        self.change_horizontal = Some(change(horizontal.kind, self.accel.x, value, term));
+                    }
+                    if let Some(vertical) = vertical {
+                        let value = vertical.value.eval(&params, context);
+                        self.change_vertical = Some(change(vertical.kind, self.accel.y, value, term));
+                    }
+                }
+                Step::Wait(frames) => {
+                    thread.wait = frames.eval(&params, context).max(0.0) as u32;
+                    return;
+                }
+                Step::Vanish => {
+                    self.is_vanished = true;
+                    return;
+                }
+            }
+        }
+    }
+
+    fn fire(&mut self, script: &BulletMl, call: &Call, params: &[f32], context: &mut Context) -> Shot {
+        let fire_params = call_params(call, params, context);
+        let fire = &script.fires[call.index];
+        let bullet_params = call_params(&fire.bullet, &fire_params, context);
+        let bullet = &script.bullets[fire.bullet.index];
+
+        // the fire's own direction and speed win over the bullet's
+        let direction = match (&fire.direction, &bullet.direction) {
+            (Some(direction), _) => Some((direction, &fire_params)),
+            (None, Some(direction)) => Some((direction, &bullet_params)),
+            (None, None) => None,
+        };
+        let direction = match direction {
+            Some((direction, params)) => {
+                let value = direction.value.eval(params, context);
+                match direction.kind {
+                    DirectionKind::Aim => context.aim() + value,
+                    DirectionKind::Absolute => value,
+                    DirectionKind::Relative => self.direction + value,
+                    DirectionKind::Sequence => self.last_direction + value,
+                }
+            }
+            None => context.aim(),
+        };
+        let speed = match (&fire.speed, &bullet.speed) {
+            (Some(speed), _) => Some((speed, &fire_params)),
+            (None, Some(speed)) => Some((speed, &bullet_params)),
+            (None, None) => None,
+        };
+        let speed = match speed {
+            Some((speed, params)) => {
+                let value = speed.value.eval(params, context);
+                match speed.kind {
+                    SpeedKind::Absolute => value,
+                    SpeedKind::Relative => self.speed + value,
+                    SpeedKind::Sequence => self.last_speed + value,
+                }
+            }
+            None => 1.0,
+        };
+        self.last_direction = direction;
+        self.last_speed = speed;
+
+        let mut runner = BulletMlRunner::new(self.script.clone());
+        runner.is_started = true;
+        runner.direction = direction;
+        runner.speed = speed;
+        runner.threads = bullet.actions.iter()
+            .map(|action| Thread::new(action.index, call_params(action, &bullet_params, context)))
+            .collect();
+
+        Shot {
+            velocity: runner.velocity(),
+            runner: (!runner.threads.is_empty()).then_some(runner),
+        }
+    }
+}
+
+// target changes spread the difference over the term, sequence adds every frame
+fn change(kind: SpeedKind, current: f32, value: f32, term: u32) -> Change {
+    let step = match kind {
+        SpeedKind::Absolute => (value - current) / term as f32,
+        SpeedKind::Relative => value / term as f32,
+        SpeedKind::Sequence => value,
+    };
+    Change { step, left: term }
+}
+
+// enemies fire from their scripts and script bullets steer themselves
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+pub fn bulletml_system(
+    mut commands: Commands,
+    mut bullet_pool: ResMut<BulletPool>,
+    mut runners: Query<(Entity, &Transform, &mut BulletMlRunner, Option<&mut Velocity>, Has<FromEnemyShoot>)>,
+    player: Query<&Transform, With<Player>>,
+    scripts: Res<Assets<BulletMl>>,
+    speed_control: Res<SpeedControl>,
+    mut game_rng: ResMut<GameRng>,
+    mut sound_events: EventWriter<SoundEvent>,
+) {
+    let target = player.get_single().ok().map(|player| player.translation.truncate());
+    let rng = game_rng.stream(RngStream::EnemyFire);
+    let mut rand = || rng.gen::<f32>();
+    // units per frame to units per second
+    let scale = FIXED_UPDATE_HZ as f32 * speed_control.value;
+
+    for (entity, transform, mut runner, velocity, is_bullet) in runners.iter_mut() {
+        let Some(script) = scripts.get(&runner.script) else {
+            continue;
+        };
+        let origin = transform.translation.truncate();
+        let mut context = Context { position: origin, target, rank: BULLETML_RANK, rand: &mut rand };
+        let shots = runner.step(script, &mut context);
+
+        if !shots.is_empty() && !is_bullet {
+            sound_events.send(SoundEvent::EnemyShoot);
+        }
+        for shot in shots {
+            let bundle = (
+                Velocity { x: shot.velocity.x * scale, y: shot.velocity.y * scale },
+                Hitbox::circle(SHOOT_RADIUS),
+                FromEnemyShoot,
+            );
+            match shot.runner {
+                Some(bullet) => bullet_pool.spawn(&mut commands, origin.extend(0.0), (bundle, bullet)),
+                None => bullet_pool.spawn(&mut commands, origin.extend(0.0), bundle),
+            };
+        }
+
+        if !is_bullet {
+            continue;
+        }
+        if runner.is_vanished() {
+            bullet_pool.release(&mut commands, entity);
+        } else if let Some(mut velocity) = velocity {
+            let direction = runner.velocity() * scale;
+            velocity.x = direction.x;
+            velocity.y = direction.y;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RING: &str = include_str!("../../assets/bulletml/ring.xml");
+    const RANK_SPREAD: &str = include_str!("../../assets/bulletml/rank_spread.xml");
+    const CURVE: &str = include_str!("../../assets/bulletml/curve.xml");
+
+    // runs frames with the player straight below and rand fixed at 0.5
+    fn run(script: &BulletMl, runner: &mut BulletMlRunner, frames: usize, rank: f32) -> Vec<Vec<Shot>> {
+        let mut rand = || 0.5;
+        (0..frames).map(|_| {
+            let mut context = Context { position: Vec2::ZERO, target: Some(Vec2::new(0.0, -100.0)), rank, rand: &mut rand };
+            runner.step(script, &mut context)
+        }).collect()
+    }
+
+    fn degrees(velocity: Vec2) -> f32 {
+        (90.0 - velocity.y.atan2(velocity.x).to_degrees()).rem_euclid(360.0)
+    }
+
+    fn eval(text: &str, context: &mut Context) -> f32 {
+        Expr::parse(text).unwrap().eval(&[3.0, 4.0], context)
+    }
+
+    #[test]
+    fn expressions() {
+        let mut rand = || 0.25;
+        let mut context = Context { position: Vec2::ZERO, target: None, rank: 0.5, rand: &mut rand };
+
+        assert_eq!(eval("1 + 2 * 3", &mut context), 7.0);
+        assert_eq!(eval("(1 + 2) * 3", &mut context), 9.0);
+        assert_eq!(eval("-$1 * $2", &mut context), -12.0);
+        assert_eq!(eval("10 % 4 - -1", &mut context), 3.0);
+        assert_eq!(eval("$rank * 4 + $rand", &mut context), 2.25);
+        assert_eq!(eval("$3", &mut context), 0.0);
+        assert!(Expr::parse("1 +").is_err());
+        assert!(Expr::parse("$foo").is_err());
+        assert!(Expr::parse("(1").is_err());
+    }
+
+    #[test]
+    fn reference_files_parse() {
+        for text in [RING, RANK_SPREAD, CURVE] {
+            assert!(BulletMl::parse(text.as_bytes()).is_ok());
+        }
+    }
+
+    #[test]
+    fn bad_scripts() {
+        let missing_top = r#"<bulletml><action label="other"><vanish/></action></bulletml>"#;
+        assert!(BulletMl::parse(missing_top.as_bytes()).is_err());
+        let missing_label = r#"<bulletml><action label="top"><fireRef label="nope"/></action></bulletml>"#;
+        assert!(BulletMl::parse(missing_label.as_bytes()).is_err());
+        let not_bulletml = r#"<stage/>"#;
+        assert!(BulletMl::parse(not_bulletml.as_bytes()).is_err());
+        let horizontal = r#"<bulletml type="horizontal"><action label="top"><vanish/></action></bulletml>"#;
+        assert!(BulletMl::parse(horizontal.as_bytes()).is_err());
+        let none = r#"<bulletml type="none"><action label="top"><vanish/></action></bulletml>"#;
+        assert!(BulletMl::parse(none.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn ring() {
+        let script = BulletMl::parse(RING.as_bytes()).unwrap();
+        let mut runner = BulletMlRunner::new(Handle::default());
+        let frames = run(&script, &mut runner, 121, 0.5);
+
+        assert_eq!(frames[0].len(), 12);
+        for (i, shot) in frames[0].iter().enumerate() {
+            assert!((degrees(shot.velocity) - i as f32 * 30.0).abs() < 0.01);
+            assert!((shot.velocity.length() - 1.5).abs() < 0.001);
+            assert!(shot.runner.is_none());
+        }
+        // one ring every 60 frames
+        let fired: Vec<usize> = frames.iter().enumerate().filter(|(_, shots)| !shots.is_empty()).map(|(i, _)| i).collect();
+        assert_eq!(fired, vec![0, 60, 120]);
+    }
+
+    #[test]
+    fn rank_spread() {
+        let script = BulletMl::parse(RANK_SPREAD.as_bytes()).unwrap();
+
+        // 2 + rank * 4 each side plus the aimed one
+        let mut runner = BulletMlRunner::new(Handle::default());
+        let frames = run(&script, &mut runner, 1, 0.5);
+        assert_eq!(frames[0].len(), 9);
+        let mut runner = BulletMlRunner::new(Handle::default());
+        let frames = run(&script, &mut runner, 1, 0.0);
+        assert_eq!(frames[0].len(), 5);
+
+        // aimed at the player below, 10 degrees apart
+        let shots = &frames[0];
+        assert!((degrees(shots[0].velocity) - 160.0).abs() < 0.01);
+        assert!((degrees(shots[2].velocity) - 180.0).abs() < 0.01);
+        assert!((degrees(shots[4].velocity) - 200.0).abs() < 0.01);
+        assert!((shots[1].velocity.length() - 2.0).abs() < 0.001);
+
+        // the wait shrinks with rank
+        let mut runner = BulletMlRunner::new(Handle::default());
+        let frames = run(&script, &mut runner, 91, 1.0);
+        let fired: Vec<usize> = frames.iter().enumerate().filter(|(_, shots)| !shots.is_empty()).map(|(i, _)| i).collect();
+        assert_eq!(fired, vec![0, 60]);
+    }
+
+    #[test]
+    fn curve() {
+        let script = BulletMl::parse(CURVE.as_bytes()).unwrap();
+        let mut enemy = BulletMlRunner::new(Handle::default());
+        let mut shots = run(&script, &mut enemy, 1, 0.5).remove(0);
+        assert_eq!(shots.len(), 1);
+
+        // rand 0.5 sends it straight down
+        let shot = shots.remove(0);
+        assert!((degrees(shot.velocity) - 180.0).abs() < 0.01);
+        assert!((shot.velocity.length() - 2.0).abs() < 0.001);
+        let mut bullet = shot.runner.unwrap();
+
+        // slows to a stop over 30 frames and waits
+        run(&script, &mut bullet, 30, 0.5);
+        assert!(bullet.velocity().length() < 0.001);
+        run(&script, &mut bullet, 10, 0.5);
+        assert!(bullet.velocity().length() < 0.001);
+
+        // then turns on the player and speeds up to 3 + rank * 2
+        run(&script, &mut bullet, 20, 0.5);
+        assert!((bullet.speed - 4.0).abs() < 0.001);
+        assert!((bullet.direction.rem_euclid(360.0) - 180.0).abs() < 0.01);
+
+        // the vertical accel pulls it further down the screen
+        run(&script, &mut bullet, 60, 0.5);
+        assert!((bullet.velocity().y + 5.0).abs() < 0.001);
+        assert!(!bullet.is_vanished());
+        run(&script, &mut bullet, 240, 0.5);
+        assert!(bullet.is_vanished());
+    }
+}
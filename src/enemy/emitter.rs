@@ -0,0 +1,238 @@
+use std::{f32::consts::PI, time::Duration};
+
+use bevy::{prelude::*, ecs::system::EntityCommands};
+
+use crate::define::{Acceleration, AngularVelocity, Homing, DelayedAim};
+
+// how one volley spreads, angles in radians with 0 to the right
+#[derive(Clone, Copy)]
+pub enum BulletPattern {
+    // one shot straight down
+    Down,
+    // one shot at the player
+    Aimed,
+    // count shots fanned evenly over spread, centered on the player
+    Spread { count: u32, spread: f32 },
+    // count shots evenly all the way around
+    Ring { count: u32 },
+    // arms evenly spaced streams, turning by turn every volley
+    Spiral { arms: u32, turn: f32 },
+}
+
+impl BulletPattern {
+    // rotation carries a spiral's turn from one volley to the next
+    pub fn angles(self, aim: f32, rotation: &mut f32) -> Vec<f32> {
+        match self {
+            BulletPattern::Down => vec![-PI / 2.0],
+            BulletPattern::Aimed => vec![aim],
+            BulletPattern::Spread { count, spread } => {
+                match count {
+                    0 => return vec![],
+                    1 => return vec![aim],
+                    _ => {}
+                }
+                let step = spread / (count - 1) as f32;
+                (0..count).map(|i| aim - spread / 2.0 + step * i as f32).collect()
+            }
+            BulletPattern::Ring { count } => {
+                (0..count).map(|i| i as f32 * 2.0 * PI / count as f32).collect()
+            }
+            BulletPattern::Spiral { arms, turn } => {
+                *rotation += turn;
+                (0..arms).map(|i| *rotation + i as f32 * 2.0 * PI / arms as f32).collect()
+            }
+        }
+    }
+}
+
+// straight down when there is no player to aim at
+pub fn aim_angle(origin: Vec2, target: Option<Vec2>) -> f32 {
+    let direction = target.map_or(Vec2::NEG_Y, |target| target - origin);
+    direction.y.atan2(direction.x)
+}
+
+// what an enemy fires each time its shoot_interval comes round
+#[derive(Component)]
+pub struct BulletEmitter {
+    pub pattern: BulletPattern,
+    // times SHOOT_VELOCITY
+    pub speed: f32,
+    // volleys per shot, burst_timer apart
+    pub burst: u32,
+    burst_timer: Timer,
+    burst_left: u32,
+    rotation: f32,
+    // behaviours the shots carry, speeds times SHOOT_VELOCITY like speed
+    acceleration: Option<Acceleration>,
+    angular_velocity: Option<AngularVelocity>,
+    homing: Option<Homing>,
+    delayed_aim: Option<DelayedAim>,
+}
+
+impl BulletEmitter {
+    pub fn new(pattern: BulletPattern) -> Self {
+        Self {
+            pattern,
+            speed: 1.0,
+            burst: 1,
+            burst_timer: Timer::default(),
+            burst_left: 0,
+            rotation: 0.0,
+            acceleration: None,
+            angular_velocity: None,
+            homing: None,
+            delayed_aim: None,
+        }
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn with_burst(mut self, count: u32, seconds: f32) -> Self {
+        self.burst = count.max(1);
+        self.burst_timer = Timer::from_seconds(seconds, TimerMode::Repeating);
+        self
+    }
+
+    pub fn with_acceleration(mut self, rate: f32, max_speed: f32) -> Self {
+        self.acceleration = Some(Acceleration { rate, max_speed });
+        self
+    }
+
+    pub fn with_curve(mut self, radians_per_second: f32) -> Self {
+        self.angular_velocity = Some(AngularVelocity(radians_per_second));
+        self
+    }
+
+    pub fn with_homing(mut self, turn_rate: f32) -> Self {
+        self.homing = Some(Homing { turn_rate });
+        self
+    }
+
+    pub fn with_delayed_aim(mut self, stop_seconds: f32, wait_seconds: f32, speed: f32) -> Self {
+        self.delayed_aim = Some(DelayedAim::new(stop_seconds, wait_seconds, speed));
+        self
+    }
+
+    // scale turns the SHOOT_VELOCITY multiples into units per second, each behaviour is in BulletComponents so release strips it
+    pub fn insert_behaviours(&self, bullet: &mut EntityCommands, scale: f32) {
+        if let Some(acceleration) = self.acceleration {
+            bullet.insert(Acceleration { rate: acceleration.rate * scale, max_speed: acceleration.max_speed * scale });
+        }
+        if let Some(angular_velocity) = self.angular_velocity {
+            bullet.insert(angular_velocity);
+        }
+        if let Some(homing) = self.homing {
+            bullet.insert(homing);
+        }
+        if let Some(delayed_aim) = &self.delayed_aim {
+            bullet.insert(DelayedAim { speed: delayed_aim.speed * scale, ..delayed_aim.clone() });
+        }
+    }
+
+    // true when a volley goes out this tick, the first of a burst fires as soon as it is triggered
+    pub fn tick(&mut self, delta: Duration, is_triggered: bool) -> bool {
+        if is_triggered {
+            self.burst_left = self.burst;
+            self.burst_timer.reset();
+        } else if self.burst_left == 0 || !self.burst_timer.tick(delta).just_finished() {
+            return false;
+        }
+        self.burst_left -= 1;
+        true
+    }
+
+    pub fn angles(&mut self, aim: f32) -> Vec<f32> {
+        self.pattern.angles(aim, &mut self.rotation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_angles(angles: Vec<f32>, expected: &[f32]) {
+        assert_eq!(angles.len(), expected.len());
+        for (angle, expected) in angles.iter().zip(expected) {
+            assert!((angle - expected).abs() < 1e-5, "{:?} != {:?}", angles, expected);
+        }
+    }
+
+    #[test]
+    fn spread_of_one_is_aimed() {
+        let mut rotation = 0.0;
+        assert_angles(BulletPattern::Spread { count: 1, spread: 1.0 }.angles(0.3, &mut rotation), &[0.3]);
+        assert_angles(BulletPattern::Spread { count: 3, spread: 1.0 }.angles(0.5, &mut rotation), &[0.0, 0.5, 1.0]);
+    }
+
+    #[test]
+    fn zero_count_fires_nothing() {
+        let mut rotation = 0.0;
+        assert!(BulletPattern::Spread { count: 0, spread: 1.0 }.angles(0.3, &mut rotation).is_empty());
+        assert!(BulletPattern::Ring { count: 0 }.angles(0.3, &mut rotation).is_empty());
+        assert!(BulletPattern::Spiral { arms: 0, turn: 0.25 }.angles(0.3, &mut rotation).is_empty());
+    }
+
+    #[test]
+    fn ring_is_evenly_spaced() {
+        let mut rotation = 0.0;
+        // the aim does not turn a ring
+        assert_angles(BulletPattern::Ring { count: 4 }.angles(1.0, &mut rotation), &[0.0, PI / 2.0, PI, PI * 1.5]);
+        assert_eq!(rotation, 0.0);
+    }
+
+    #[test]
+    fn spiral_carries_rotation() {
+        let mut emitter = BulletEmitter::new(BulletPattern::Spiral { arms: 2, turn: 0.25 });
+        assert_angles(emitter.angles(0.0), &[0.25, 0.25 + PI]);
+        assert_angles(emitter.angles(0.0), &[0.5, 0.5 + PI]);
+        assert_angles(emitter.angles(0.0), &[0.75, 0.75 + PI]);
+    }
+
+    #[test]
+    fn aim_falls_back_to_down() {
+        assert_eq!(aim_angle(Vec2::ZERO, None), -PI / 2.0);
+        assert!((aim_angle(Vec2::ZERO, Some(Vec2::new(0.0, 5.0))) - PI / 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn single_volley_fires_only_on_trigger() {
+        let mut emitter = BulletEmitter::new(BulletPattern::Down);
+        assert!(!emitter.tick(Duration::from_secs(1), false));
+        assert!(emitter.tick(Duration::ZERO, true));
+        assert!(!emitter.tick(Duration::from_secs(1), false));
+    }
+
+    #[test]
+    fn burst_follows_trigger() {
+        let mut emitter = BulletEmitter::new(BulletPattern::Down).with_burst(3, 0.25);
+        let half = Duration::from_millis(125);
+        let full = Duration::from_millis(250);
+
+        // first volley on the trigger, then burst_timer apart
+        assert!(emitter.tick(Duration::ZERO, true));
+        assert!(!emitter.tick(half, false));
+        assert!(emitter.tick(half, false));
+        assert!(emitter.tick(full, false));
+        assert!(!emitter.tick(full, false));
+        assert!(!emitter.tick(full, false));
+    }
+
+    #[test]
+    fn retrigger_restarts_burst() {
+        let mut emitter = BulletEmitter::new(BulletPattern::Down).with_burst(3, 0.25);
+        let half = Duration::from_millis(125);
+        let full = Duration::from_millis(250);
+
+        assert!(emitter.tick(Duration::ZERO, true));
+        assert!(!emitter.tick(half, false));
+        // the timer starts over with the new trigger, not half way through
+        assert!(emitter.tick(Duration::ZERO, true));
+        assert!(!emitter.tick(half, false));
+        assert!(emitter.tick(half, false));
+        assert!(emitter.tick(full, false));
+        assert!(!emitter.tick(full, false));
+    }
+}
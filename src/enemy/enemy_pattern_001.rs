@@ -1,16 +1,16 @@
-use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use bevy::prelude::*;
 
-use crate::define::*;
+use crate::{define::*, stage::EnemyPatternKind};
 
-use super::{EnemySchedule, get_shoot_duration};
+use super::{EnemySpawnEvent, emitter::{BulletEmitter, BulletPattern}};
 
 pub struct EnemyPattern001;
 
 impl Plugin for EnemyPattern001 {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (
+        app.add_systems(FixedUpdate, (
             enemy_spawn_pattern_001,
-        ));
+        ).chain().in_set(EnemyPatternKind::Pattern001).run_if(in_state(GameState::InGame)));
     }
 }
 
@@ -19,43 +19,28 @@ pub struct EnemyMovePattern001;
 
 fn enemy_spawn_pattern_001(
     mut commands: Commands,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    window_size_limit: Res<WindowSizeLimit>,
-    mut enemy_schedule: ResMut<EnemySchedule>,
-    game_timer: Res<GameTimer>,
+    mut spawn_events: EventReader<EnemySpawnEvent>,
 ) {
-    if EnemySchedule::is_ready(&mut enemy_schedule.enemy_pattern_001, game_timer.seconds) {
-        let y = window_size_limit.top + 30.0;
-        let x = window_size_limit.right / 3.0;
-
-        let enemy_positions = [
-            [x, y],
-            [x * 2.0, y],
-            [0.0, y],
-            [-x, y],
-            [-x * 2.0, y],
-        ];
-
-        for position in enemy_positions {
-            let shot_duration = get_shoot_duration();
-
-            // enemy
-            commands.spawn((
-                MaterialMesh2dBundle {
-                    mesh: meshes.add(shape::Circle::new(ENEMY_RADIUS).into()).into(),
-                    material: materials.add(ColorMaterial::from(Color::GREEN)),
-                    transform: Transform::from_xyz(position[0], position[1], 9.0),
-                    ..default()
-                },
-                Enemy {
-                    shoot_interval: Timer::from_seconds(shot_duration, TimerMode::Repeating),
-                    point: 1.0,
-                },
-                AutoDespawn,
-                Velocity {x: 0.0, y: -0.5},
-                EnemyMovePattern001,
-            ));
-        }
+    for event in EnemySpawnEvent::read(&mut spawn_events, EnemyPatternKind::Pattern001) {
+        let (x, y) = (event.x, event.y);
+
+        // enemy
+        let mut enemy = commands.spawn((
+            SpatialBundle::from_transform(Transform::from_xyz(x, y, 9.0)),
+            Enemy {
+                shoot_interval: Timer::from_seconds(event.shoot_duration, TimerMode::Repeating),
+                point: event.point,
+                drop_chance: event.drop_chance,
+            },
+            Health::new(event.health),
+            Hitbox::circle(ENEMY_RADIUS),
+            AutoDespawn,
+            // one shot straight down
+            BulletEmitter::new(BulletPattern::Down),
+            Velocity {x: event.velocity.0, y: event.velocity.1},
+            EnemyMovePattern001,
+            OnGameScreen,
+        ));
+        event.attach_script(&mut enemy);
     }
 }
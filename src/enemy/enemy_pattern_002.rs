@@ -1,17 +1,17 @@
-use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use bevy::prelude::*;
 
-use crate::define::*;
+use crate::{define::*, stage::EnemyPatternKind};
 
-use super::{EnemySchedule, get_shoot_duration};
+use super::{EnemySpawnEvent, emitter::{BulletEmitter, BulletPattern}};
 
 pub struct EnemyPattern002;
 
 impl Plugin for EnemyPattern002 {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (
+        app.add_systems(FixedUpdate, (
             enemy_spawn_pattern_002,
             enemy_move_pattern_002,
-        ));
+        ).chain().in_set(EnemyPatternKind::Pattern002).run_if(in_state(GameState::InGame)));
     }
 }
 
@@ -20,44 +20,41 @@ pub struct EnemyMovePattern002;
 
 fn enemy_spawn_pattern_002(
     mut commands: Commands,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    window_size_limit: Res<WindowSizeLimit>,
-    mut enemy_schedule: ResMut<EnemySchedule>,
-    game_timer: Res<GameTimer>,
+    mut spawn_events: EventReader<EnemySpawnEvent>,
 ) {
-    if EnemySchedule::is_ready(&mut enemy_schedule.enemy_pattern_002, game_timer.seconds) {
-        let y = window_size_limit.top + 30.0;
-
-        let shot_duration = get_shoot_duration();
+    for event in EnemySpawnEvent::read(&mut spawn_events, EnemyPatternKind::Pattern002) {
+        let (x, y) = (event.x, event.y);
 
         // enemy
-        commands.spawn((
-            MaterialMesh2dBundle {
-                mesh: meshes.add(shape::Circle::new(ENEMY_RADIUS).into()).into(),
-                material: materials.add(ColorMaterial::from(Color::GREEN)),
-                transform: Transform::from_xyz(0.0, y, 9.0),
-                ..default()
-            },
+        let mut enemy = commands.spawn((
+            SpatialBundle::from_transform(Transform::from_xyz(x, y, 9.0)),
             Enemy {
-                shoot_interval: Timer::from_seconds(shot_duration, TimerMode::Repeating),
-                point: 1.0,
+                shoot_interval: Timer::from_seconds(event.shoot_duration, TimerMode::Repeating),
+                point: event.point,
+                drop_chance: event.drop_chance,
             },
+            Health::new(event.health),
+            Hitbox::circle(ENEMY_RADIUS),
             AutoDespawn,
-            Velocity {x: 0.0, y: -0.5},
+            // one shot at the player, slow off the mark then speeding up
+            BulletEmitter::new(BulletPattern::Aimed).with_speed(0.4).with_acceleration(1.5, 1.5),
+            Velocity {x: event.velocity.0, y: event.velocity.1},
             EnemyMovePattern002,
+            OnGameScreen,
         ));
+        event.attach_script(&mut enemy);
     }
 }
 
 fn enemy_move_pattern_002(
     mut query: Query<(&mut Velocity, &Transform), With<EnemyMovePattern002>>,
-    time: Res<Time<Virtual>>,
+    time: Res<Time>,
 ) {
     let sin = time.elapsed_seconds().sin();
 
     let x = sin * 100.0;
     for (mut velocity, transform) in query.iter_mut() {
-        velocity.x = x - transform.translation.x;
+        // reach the target x by the next tick
+        velocity.x = (x - transform.translation.x) / time.delta_seconds();
     }
 }
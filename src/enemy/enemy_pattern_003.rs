@@ -1,17 +1,17 @@
-use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use bevy::prelude::*;
 
-use crate::define::*;
+use crate::{define::*, stage::EnemyPatternKind};
 
-use super::{EnemySchedule, get_shoot_duration};
+use super::{EnemySpawnEvent, emitter::{BulletEmitter, BulletPattern}};
 
 pub struct EnemyPattern003;
 
 impl Plugin for EnemyPattern003 {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (
+        app.add_systems(FixedUpdate, (
             enemy_spawn_pattern_003,
             enemy_move_pattern_003,
-        ));
+        ).chain().in_set(EnemyPatternKind::Pattern003).run_if(in_state(GameState::InGame)));
     }
 }
 
@@ -20,49 +20,35 @@ pub struct EnemyMovePattern003;
 
 fn enemy_spawn_pattern_003(
     mut commands: Commands,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    window_size_limit: Res<WindowSizeLimit>,
-    mut enemy_schedule: ResMut<EnemySchedule>,
-    game_timer: Res<GameTimer>,
+    mut spawn_events: EventReader<EnemySpawnEvent>,
 ) {
-    if EnemySchedule::is_ready(&mut enemy_schedule.enemy_pattern_003, game_timer.seconds) {
-        let shot_duration = get_shoot_duration();
-
-        struct Position {
-            x: f32,
-            y: f32,
-            velocity_x: f32,
-        }
-
-        let enemy_positions = [
-            Position {x: 40.0, y: window_size_limit.top + 30.0, velocity_x: -0.2},
-            Position {x: -40.0, y: window_size_limit.top + 50.0, velocity_x: 0.2},
-        ];
-
-        for position in enemy_positions {
-            // enemy
-            commands.spawn((
-                MaterialMesh2dBundle {
-                    mesh: meshes.add(shape::Circle::new(ENEMY_RADIUS).into()).into(),
-                    material: materials.add(ColorMaterial::from(Color::GREEN)),
-                    transform: Transform::from_xyz(position.x, position.y, 9.0),
-                    ..default()
-                },
-                Enemy {
-                    shoot_interval: Timer::from_seconds(shot_duration, TimerMode::Repeating),
-                    point: 2.0,
-                },
-                AutoDespawn,
-                Velocity {x: position.velocity_x, y: 0.0},
-                EnemyMovePattern003,
-            ));
-        }
+    for event in EnemySpawnEvent::read(&mut spawn_events, EnemyPatternKind::Pattern003) {
+        let (x, y) = (event.x, event.y);
+
+        // enemy
+        let mut enemy = commands.spawn((
+            SpatialBundle::from_transform(Transform::from_xyz(x, y, 9.0)),
+            Enemy {
+                shoot_interval: Timer::from_seconds(event.shoot_duration, TimerMode::Repeating),
+                point: event.point,
+                drop_chance: event.drop_chance,
+            },
+            Health::new(event.health),
+            Hitbox::circle(ENEMY_RADIUS),
+            AutoDespawn,
+            // three-way fan at the player
+            BulletEmitter::new(BulletPattern::Spread { count: 3, spread: 0.6 }),
+            Velocity {x: event.velocity.0, y: event.velocity.1},
+            EnemyMovePattern003,
+            OnGameScreen,
+        ));
+        event.attach_script(&mut enemy);
     }
 }
 
 fn enemy_move_pattern_003(
     mut query: Query<(&mut Velocity, &mut Transform), With<EnemyMovePattern003>>,
+    time: Res<Time>,
 ) {
     // y = 0.1x^2 + 10x -200
     for (mut velocity, transform) in query.iter_mut() {
@@ -70,6 +56,6 @@ fn enemy_move_pattern_003(
         let org_x = transform.translation.x;
         let y = 0.1 * (org_x * org_x) + (10.0 * org_x) - 200.0;
 
-        velocity.y = y - org_y;
+        velocity.y = (y - org_y) / time.delta_seconds();
     }
 }
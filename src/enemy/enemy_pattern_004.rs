@@ -1,65 +1,62 @@
 use std::f32::consts::PI;
 
-use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use bevy::prelude::*;
 
-use crate::define::*;
+use crate::{define::*, stage::EnemyPatternKind};
 
-use super::{EnemySchedule, get_shoot_duration};
+use super::{EnemySpawnEvent, emitter::{BulletEmitter, BulletPattern}};
 
 pub struct EnemyPattern004;
 
 impl Plugin for EnemyPattern004 {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (
+        app.add_systems(FixedUpdate, (
             enemy_spawn_pattern_004,
             enemy_move_pattern_004,
-        ));
+        ).chain().in_set(EnemyPatternKind::Pattern004).run_if(in_state(GameState::InGame)));
     }
 }
 
 #[derive(Component)]
 pub struct EnemyMovePattern004 {
+    base_x: f32,
     base_y: f32,
 }
 
 fn enemy_spawn_pattern_004(
     mut commands: Commands,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    window_size_limit: Res<WindowSizeLimit>,
-    mut enemy_schedule: ResMut<EnemySchedule>,
-    game_timer: Res<GameTimer>,
+    mut spawn_events: EventReader<EnemySpawnEvent>,
 ) {
-    if EnemySchedule::is_ready(&mut enemy_schedule.enemy_pattern_004, game_timer.seconds) {
-        let shot_duration = get_shoot_duration();
-
-        let x = 0.0;
-        let y = window_size_limit.top + 30.0;
+    for event in EnemySpawnEvent::read(&mut spawn_events, EnemyPatternKind::Pattern004) {
+        let (x, y) = (event.x, event.y);
 
         // enemy
-        commands.spawn((
-            MaterialMesh2dBundle {
-                mesh: meshes.add(shape::Circle::new(ENEMY_RADIUS).into()).into(),
-                material: materials.add(ColorMaterial::from(Color::GREEN)),
-                transform: Transform::from_xyz(x, y, 9.0),
-                ..default()
-            },
+        let mut enemy = commands.spawn((
+            SpatialBundle::from_transform(Transform::from_xyz(x, y, 9.0)),
             Enemy {
-                shoot_interval: Timer::from_seconds(shot_duration, TimerMode::Repeating),
-                point: 1.0,
+                shoot_interval: Timer::from_seconds(event.shoot_duration, TimerMode::Repeating),
+                point: event.point,
+                drop_chance: event.drop_chance,
             },
+            Health::new(event.health),
+            Hitbox::circle(ENEMY_RADIUS),
             AutoDespawn,
-            Velocity {x: 0.0, y: -0.5},
+            // three quick shots at the player that bend after it a little
+            BulletEmitter::new(BulletPattern::Aimed).with_burst(3, 0.15).with_homing(0.6),
+            Velocity {x: event.velocity.0, y: event.velocity.1},
             EnemyMovePattern004 {
+                base_x: x,
                 base_y: y,
             },
+            OnGameScreen,
         ));
+        event.attach_script(&mut enemy);
     }
 }
 
 fn enemy_move_pattern_004(
     mut query: Query<(&Velocity, &mut Transform, &mut EnemyMovePattern004), With<EnemyMovePattern004>>,
-    time: Res<Time<Virtual>>,
+    time: Res<Time>,
 ) {
     let theta = (time.elapsed_seconds() * 100.0) % 360.0;
 
@@ -69,8 +66,8 @@ fn enemy_move_pattern_004(
     let y = radian.sin() * 100.0;
 
     for (velocity, mut transform, mut enemy_move) in query.iter_mut() {
-        transform.translation.x = x;
+        transform.translation.x = enemy_move.base_x + x;
         transform.translation.y = enemy_move.base_y + y;
-        enemy_move.base_y += velocity.y;
+        enemy_move.base_y += velocity.y * time.delta_seconds();
     }
 }
@@ -1,18 +1,18 @@
-use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use bevy::prelude::*;
 
-use crate::define::*;
+use crate::{define::*, stage::EnemyPatternKind};
 
-use super::{EnemySchedule, get_shoot_duration};
+use super::{EnemySpawnEvent, emitter::{BulletEmitter, BulletPattern}};
 
 pub struct EnemyPattern006;
 
 impl Plugin for EnemyPattern006 {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (
+        app.add_systems(FixedUpdate, (
             enemy_spawn_pattern_006,
             enemy_move_pattern_006,
             enemy_wait_and_go,
-        ));
+        ).chain().in_set(EnemyPatternKind::Pattern006).run_if(in_state(GameState::InGame)));
     }
 }
 
@@ -26,73 +26,59 @@ pub struct WaitTimer {
 
 fn enemy_spawn_pattern_006(
     mut commands: Commands,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    window_size_limit: Res<WindowSizeLimit>,
-    mut enemy_schedule: ResMut<EnemySchedule>,
-    game_timer: Res<GameTimer>,
+    mut spawn_events: EventReader<EnemySpawnEvent>,
 ) {
-    if EnemySchedule::is_ready(&mut enemy_schedule.enemy_pattern_006, game_timer.seconds) {
-        let y = window_size_limit.top + 30.0;
-
-        struct Position {
-            x: f32,
-            y: f32,
-        }
+    for event in EnemySpawnEvent::read(&mut spawn_events, EnemyPatternKind::Pattern006) {
+        let (x, y) = (event.x, event.y);
 
-        let positions = [
-            Position {x: 80.0, y},
-            Position {x: -80.0, y},
-        ];
-
-        for position in positions {
-            let shot_duration = get_shoot_duration();
-            // enemy
-            commands.spawn((
-                MaterialMesh2dBundle {
-                    mesh: meshes.add(shape::Circle::new(ENEMY_RADIUS).into()).into(),
-                    material: materials.add(ColorMaterial::from(Color::GREEN)),
-                    transform: Transform::from_xyz(position.x, position.y, 9.0),
-                    ..default()
-                },
-                Enemy {
-                    shoot_interval: Timer::from_seconds(shot_duration, TimerMode::Repeating),
-                    point: 1.0,
-                },
-                AutoDespawn,
-                Velocity {x: 0.0, y: -2.0},
-                EnemyMovePattern006,
-            ));
-        }
+        // enemy
+        let mut enemy = commands.spawn((
+            SpatialBundle::from_transform(Transform::from_xyz(x, y, 9.0)),
+            Enemy {
+                shoot_interval: Timer::from_seconds(event.shoot_duration, TimerMode::Repeating),
+                point: event.point,
+                drop_chance: event.drop_chance,
+            },
+            Health::new(event.health),
+            Hitbox::circle(ENEMY_RADIUS),
+            AutoDespawn,
+            // short three-arm spiral with curling shots
+            BulletEmitter::new(BulletPattern::Spiral { arms: 3, turn: 0.4 }).with_burst(6, 0.1).with_curve(0.8),
+            Velocity {x: event.velocity.0, y: event.velocity.1},
+            EnemyMovePattern006,
+            OnGameScreen,
+        ));
+        event.attach_script(&mut enemy);
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn enemy_move_pattern_006(
     mut commands: Commands,
-    mut query: Query<(&mut Velocity, &mut Transform, Entity), With<EnemyMovePattern006>>,
+    mut query: Query<(&mut Velocity, &mut Transform, Entity), (With<EnemyMovePattern006>, Without<WaitTimer>)>,
 ) {
     for (mut velocity, mut transform, entity) in query.iter_mut() {
-        if transform.translation.y == -100.0 {
+        if transform.translation.y <= -100.0 {
             (velocity.x, velocity.y) = (0.0, 0.0);
 
             commands.entity(entity).insert(WaitTimer{
                 timer: Timer::from_seconds(3.0, TimerMode::Once),
             });
-            transform.translation.y = -100.1;
+            transform.translation.y = -100.0;
         }
     }
 }
 
 fn enemy_wait_and_go(
     mut query: Query<(&mut Velocity, &Transform, &mut WaitTimer), With<EnemyMovePattern006>>,
-    time: Res<Time<Virtual>>,
+    time: Res<Time>,
 ) {
     for (mut velocity, transform, mut wait_timer) in query.iter_mut() {
         if wait_timer.timer.tick(time.delta()).finished() {
             if transform.translation.x >= 0.0 {
-                (velocity.x, velocity.y) = (3.0, 3.0)
+                (velocity.x, velocity.y) = (180.0, 180.0)
             } else {
-                (velocity.x, velocity.y) = (-3.0, 3.0)
+                (velocity.x, velocity.y) = (-180.0, 180.0)
             }
         }
     }
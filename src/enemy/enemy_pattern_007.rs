@@ -1,16 +1,16 @@
-use bevy::{prelude::*, sprite::MaterialMesh2dBundle, math::{cubic_splines::CubicCurve, vec3}};
+use bevy::{prelude::*, math::{cubic_splines::CubicCurve, vec3}};
 
-use crate::define::*;
+use crate::{define::*, stage::EnemyPatternKind};
 
-use super::{EnemySchedule, get_shoot_duration};
+use super::{EnemySpawnEvent, emitter::{BulletEmitter, BulletPattern}};
 pub struct EnemyPattern007;
 
 impl Plugin for EnemyPattern007 {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (
+        app.add_systems(FixedUpdate, (
             enemy_spawn_pattern_007,
             enemy_move_pattern_007,
-        ));
+        ).chain().in_set(EnemyPatternKind::Pattern007).run_if(in_state(GameState::InGame)));
     }
 }
 
@@ -22,42 +22,40 @@ pub struct Curve(CubicCurve<Vec3>);
 
 fn enemy_spawn_pattern_007(
     mut commands: Commands,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    window_size_limit: Res<WindowSizeLimit>,
-    mut enemy_schedule: ResMut<EnemySchedule>,
-    game_timer: Res<GameTimer>,
+    mut spawn_events: EventReader<EnemySpawnEvent>,
 ) {
-    if EnemySchedule::is_ready(&mut enemy_schedule.enemy_pattern_007, game_timer.seconds) {
-        let shot_duration = get_shoot_duration();
+    for event in EnemySpawnEvent::read(&mut spawn_events, EnemyPatternKind::Pattern007) {
+        let (x, y) = (event.x, event.y);
 
-        let x = window_size_limit.right + (ENEMY_RADIUS * 2.0);
+        // curve from the spawn position to the mirrored position
         let points = [[
-            vec3(-x,0.0, 0.0),
-            vec3(x * 2.0, 240.0, 0.0),
-            vec3(-x * 2.0, 240.0, 0.0),
-            vec3(x, 0.0, 0.0),
+            vec3(x, y, 0.0),
+            vec3(-x * 2.0, y + 240.0, 0.0),
+            vec3(x * 2.0, y + 240.0, 0.0),
+            vec3(-x, y, 0.0),
         ]];
-    
+
         let bezier = CubicBezier::new(points).to_curve();
-    
+
         // enemy
-        commands.spawn((
-            MaterialMesh2dBundle {
-                mesh: meshes.add(shape::Circle::new(ENEMY_RADIUS).into()).into(),
-                material: materials.add(ColorMaterial::from(Color::GREEN)),
-                transform: Transform::from_translation(points[0][0]),
-                ..default()
-            },
+        let mut enemy = commands.spawn((
+            SpatialBundle::from_transform(Transform::from_translation(points[0][0])),
             Enemy {
-                shoot_interval: Timer::from_seconds(shot_duration, TimerMode::Repeating),
-                point: 1.0,
+                shoot_interval: Timer::from_seconds(event.shoot_duration, TimerMode::Repeating),
+                point: event.point,
+                drop_chance: event.drop_chance,
             },
+            Health::new(event.health),
+            Hitbox::circle(ENEMY_RADIUS),
             AutoDespawn,
-            Velocity {x: 0.0, y: 0.0},
+            // wide five-way fan
+            BulletEmitter::new(BulletPattern::Spread { count: 5, spread: 1.2 }),
+            Velocity {x: event.velocity.0, y: event.velocity.1},
             EnemyMovePattern007,
             Curve(bezier),
+            OnGameScreen,
         ));
+        event.attach_script(&mut enemy);
     }
 }
 
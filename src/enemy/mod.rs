@@ -1,7 +1,7 @@
-use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use bevy::{prelude::*, ecs::system::EntityCommands};
 use rand::Rng;
 
-use crate::{Enemy, AutoDespawn, SpeedControl, SHOOT_RADIUS, SHOOT_VELOCITY, Velocity, FromEnemyShoot};
+use crate::{common::BulletPool, Enemy, Player, SpeedControl, SHOOT_VELOCITY, SHOOT_RADIUS, Hitbox, Velocity, FromEnemyShoot, GameState, GameSet, GameTimer, stage::{Stage, StageHandle, EnemyPatternKind}, rng::{GameRng, RngStream}, sound::SoundEvent};
 
 use self::{
     enemy_pattern_001::EnemyPattern001,
@@ -11,6 +11,9 @@ use self::{
     enemy_pattern_005::EnemyPattern005,
     enemy_pattern_006::EnemyPattern006,
     enemy_pattern_007::EnemyPattern007,
+    boss::BossPlugin,
+    emitter::{BulletEmitter, aim_angle},
+    bulletml::{BulletMl, BulletMlLoader, BulletMlRunner, bulletml_system},
 };
 
 mod enemy_pattern_001;
@@ -20,49 +23,66 @@ mod enemy_pattern_004;
 mod enemy_pattern_005;
 mod enemy_pattern_006;
 mod enemy_pattern_007;
+pub mod boss;
+pub mod emitter;
+pub mod bulletml;
 
 pub struct EnemyPlugin;
 
+// walks the stage timeline with GameTimer
 #[derive(Resource)]
 pub struct EnemySchedule {
-    pub enemy_pattern_001: EnemyScheduleValue,
-    pub enemy_pattern_002: EnemyScheduleValue,
-    pub enemy_pattern_003: EnemyScheduleValue,
-    pub enemy_pattern_004: EnemyScheduleValue,
-    pub enemy_pattern_005: EnemyScheduleValue,
-    pub enemy_pattern_006: EnemyScheduleValue,
-    pub enemy_pattern_007: EnemyScheduleValue,
+    pub stage: Handle<Stage>,
+    pub cursor: usize,
 }
 
-impl EnemySchedule {
-    pub fn is_ready(enemy_schedule_value: &mut EnemyScheduleValue, game_time_seconds: u64) -> bool {
-        if enemy_schedule_value.enable && enemy_schedule_value.seconds <= game_time_seconds {
-            enemy_schedule_value.enable = false;
-            return true;
-        }
-        return false;
-    }
+// one enemy to spawn, shot timing already rolled so spawning does not depend on system order
+#[derive(Event, Clone)]
+pub struct EnemySpawnEvent {
+    pub pattern: EnemyPatternKind,
+    pub x: f32,
+    pub y: f32,
+    pub velocity: (f32, f32),
+    pub point: f32,
+    pub health: f32,
+    pub drop_chance: f64,
+    pub shoot_duration: f32,
+    pub script: Option<Handle<BulletMl>>,
 }
 
-pub struct EnemyScheduleValue {
-    seconds: u64,
-    enable: bool,
+// an enemy shot down, not sent when it leaves the screen
+#[derive(Event)]
+pub struct EnemyDefeatedEvent {
+    pub x: f32,
+    pub y: f32,
+    pub drop_chance: f64,
+}
+
+impl EnemySpawnEvent {
+    pub fn read(events: &mut EventReader<EnemySpawnEvent>, pattern: EnemyPatternKind) -> Vec<EnemySpawnEvent> {
+        events.read()
+            .filter(|event| event.pattern == pattern)
+            .cloned()
+            .collect()
+    }
+
+    // a BulletML script takes over from the pattern's emitter and shoot_interval, the emitter is removed so
+    // tuning it changes nothing on a scripted wave
+    pub fn attach_script(&self, enemy: &mut EntityCommands) {
+        if let Some(script) = &self.script {
+            enemy.remove::<BulletEmitter>().insert(BulletMlRunner::new(script.clone()));
+        }
+    }
 }
 
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
         app
-            .insert_resource(
-                EnemySchedule {
-                    enemy_pattern_001: EnemyScheduleValue { seconds: 5, enable: true },
-                    enemy_pattern_002: EnemyScheduleValue { seconds: 15, enable: true },
-                    enemy_pattern_003: EnemyScheduleValue { seconds: 30, enable: true },
-                    enemy_pattern_004: EnemyScheduleValue { seconds: 45, enable: true },
-                    enemy_pattern_005: EnemyScheduleValue { seconds: 60, enable: true },
-                    enemy_pattern_006: EnemyScheduleValue { seconds: 75, enable: true },
-                    enemy_pattern_007: EnemyScheduleValue { seconds: 1, enable: true },
-                }
-            )
+            .add_event::<EnemySpawnEvent>()
+            .add_event::<EnemyDefeatedEvent>()
+            .init_asset::<BulletMl>()
+            .init_asset_loader::<BulletMlLoader>()
+            .add_systems(OnTransition { from: GameState::Title, to: GameState::InGame }, enemy_schedule_setup)
             .add_plugins((
                 EnemyPattern001,
                 EnemyPattern002,
@@ -71,37 +91,167 @@ impl Plugin for EnemyPlugin {
                 EnemyPattern005,
                 EnemyPattern006,
                 EnemyPattern007,
+                BossPlugin,
             ))
-            .add_systems(Update, enemy_shoot_system);
+            .configure_sets(FixedUpdate, (
+                EnemyPatternKind::Pattern001,
+                EnemyPatternKind::Pattern002,
+                EnemyPatternKind::Pattern003,
+                EnemyPatternKind::Pattern004,
+                EnemyPatternKind::Pattern005,
+                EnemyPatternKind::Pattern006,
+                EnemyPatternKind::Pattern007,
+                EnemyPatternKind::Boss,
+            ).chain().in_set(GameSet::Enemy))
+            .add_systems(FixedUpdate, (
+                enemy_schedule_system.before(EnemyPatternKind::Pattern001),
+                enemy_shoot_system.after(EnemyPatternKind::Boss),
+                bulletml_system.after(EnemyPatternKind::Boss),
+            ).in_set(GameSet::Enemy).run_if(in_state(GameState::InGame)));
+    }
+
+    // script bullets carry their own runner, the pool is CommonPlugin's and exists once every plugin is built
+    fn finish(&self, app: &mut App) {
+        app.world.resource_mut::<BulletPool>().register::<BulletMlRunner>();
+    }
+}
+
+fn enemy_schedule_setup(
+    mut commands: Commands,
+    stage_handle: Res<StageHandle>,
+) {
+    commands.insert_resource(EnemySchedule {
+        stage: stage_handle.0.clone(),
+        cursor: 0,
+    });
+}
+
+fn enemy_schedule_system(
+    mut enemy_schedule: ResMut<EnemySchedule>,
+    mut spawn_events: EventWriter<EnemySpawnEvent>,
+    mut game_rng: ResMut<GameRng>,
+    stages: Res<Assets<Stage>>,
+    game_timer: Res<GameTimer>,
+) {
+    let Some(stage) = stages.get(&enemy_schedule.stage) else {
+        return;
+    };
+
+    while let Some(event) = stage.events.get(enemy_schedule.cursor) {
+        if event.seconds > game_timer.seconds {
+            break;
+        }
+        for (x, y) in event.positions.iter() {
+            let (offset_x, offset_y) = get_spawn_offset(&mut game_rng, event.jitter);
+            spawn_events.send(EnemySpawnEvent {
+                pattern: event.pattern,
+                x: x + offset_x,
+                y: y + offset_y,
+                velocity: event.velocity,
+                point: event.point,
+                health: event.health,
+                drop_chance: event.drop_chance,
+                shoot_duration: get_shoot_duration(&mut game_rng, event.shoot_interval),
+                script: event.script.clone(),
+            });
+        }
+        enemy_schedule.cursor += 1;
     }
 }
 
+// every enemy fires its own emitter's pattern, the boss has its own attacks
+#[allow(clippy::too_many_arguments)]
 fn enemy_shoot_system(
     mut commands: Commands,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut query: Query<(&Transform, &mut Enemy)>,
-    time: Res<Time<Virtual>>,
+    mut bullet_pool: ResMut<BulletPool>,
+    mut query: Query<(&Transform, &mut Enemy, &mut BulletEmitter)>,
+    player: Query<&Transform, With<Player>>,
+    time: Res<Time>,
     speed_control: Res<SpeedControl>,
+    mut sound_events: EventWriter<SoundEvent>,
 ) {
-    for (enemy_transform, mut enemy) in query.iter_mut() {
-        if enemy.shoot_interval.tick(time.delta()).just_finished() {
-            // fire shooting from enemy
-            commands.spawn((
-                MaterialMesh2dBundle {
-                    mesh: meshes.add(shape::Circle::new(SHOOT_RADIUS).into()).into(),
-                    material: materials.add(ColorMaterial::from(Color::RED)),
-                    transform: Transform::from_xyz(enemy_transform.translation.x, enemy_transform.translation.y, 0.0),
-                    ..default()
-                },
-                Velocity {x: 0.0, y: -SHOOT_VELOCITY * speed_control.value },
-                AutoDespawn,
+    let target = player.get_single().ok().map(|player| player.translation.truncate());
+
+    for (enemy_transform, mut enemy, mut emitter) in query.iter_mut() {
+        let is_triggered = enemy.shoot_interval.tick(time.delta()).just_finished();
+        if !emitter.tick(time.delta(), is_triggered) {
+            continue;
+        }
+
+        // fire shooting from enemy, an empty volley makes no sound
+        let origin = enemy_transform.translation.truncate();
+        let angles = emitter.angles(aim_angle(origin, target));
+        if angles.is_empty() {
+            continue;
+        }
+        sound_events.send(SoundEvent::EnemyShoot);
+
+        let scale = SHOOT_VELOCITY * speed_control.value;
+        let speed = scale * emitter.speed;
+        for angle in angles {
+            let direction = Vec2::from_angle(angle) * speed;
+            let bullet = bullet_pool.spawn(&mut commands, origin.extend(0.0), (
+                Velocity { x: direction.x, y: direction.y },
+                Hitbox::circle(SHOOT_RADIUS),
                 FromEnemyShoot,
             ));
+            emitter.insert_behaviours(&mut commands.entity(bullet), scale);
         }
     }
 }
 
-pub fn get_shoot_duration() -> f32 {
-    return rand::thread_rng().gen_range(1.0..3.0);
-}
\ No newline at end of file
+pub fn get_shoot_duration(game_rng: &mut GameRng, shoot_interval: (f32, f32)) -> f32 {
+    game_rng.stream(RngStream::EnemyFire).gen_range(shoot_interval.0..=shoot_interval.1)
+}
+
+// waves roll their own stream, so moving a spawn never changes when anything fires
+pub fn get_spawn_offset(game_rng: &mut GameRng, jitter: (f32, f32)) -> (f32, f32) {
+    let rng = game_rng.stream(RngStream::Wave);
+    (rng.gen_range(-jitter.0..=jitter.0), rng.gen_range(-jitter.1..=jitter.1))
+}
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+    use super::emitter::BulletPattern;
+    use crate::define::{HEADLESS_FRAME_SECONDS, ENEMY_RADIUS};
+
+    // one enemy firing every frame, returns the shot sounds and the bullets fired
+    fn fire(pattern: BulletPattern) -> (usize, usize) {
+        let mut app = App::new();
+        app
+            .add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(HEADLESS_FRAME_SECONDS)))
+            .insert_resource(SpeedControl { value: 1.0 })
+            .init_resource::<BulletPool>()
+            .add_event::<SoundEvent>()
+            .add_systems(Update, enemy_shoot_system);
+        app.world.spawn((
+            SpatialBundle::default(),
+            Enemy { shoot_interval: Timer::from_seconds(HEADLESS_FRAME_SECONDS, TimerMode::Repeating), point: 1.0, drop_chance: 0.0 },
+            Hitbox::circle(ENEMY_RADIUS),
+            BulletEmitter::new(pattern),
+        ));
+
+        let mut sounds = 0;
+        for _ in 0..10 {
+            app.update();
+            sounds += app.world.resource_mut::<Events<SoundEvent>>().drain().count();
+        }
+        let bullets = app.world.query_filtered::<(), With<FromEnemyShoot>>().iter(&app.world).count();
+        (sounds, bullets)
+    }
+
+    #[test]
+    fn empty_volleys_are_silent() {
+        assert_eq!(fire(BulletPattern::Ring { count: 0 }), (0, 0));
+        assert_eq!(fire(BulletPattern::Spread { count: 0, spread: 1.0 }), (0, 0));
+
+        let (sounds, bullets) = fire(BulletPattern::Ring { count: 4 });
+        assert!(sounds > 0);
+        assert_eq!(bullets, sounds * 4);
+    }
+}
@@ -0,0 +1,322 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{define::*, input::{GameInput, GameButton, KeyBindings}, screen::{despawn_screen, screen_node, screen_text}, settings::{data_path, write_atomic}};
+
+pub const HIGH_SCORE_VERSION: u32 = 1;
+pub const HIGH_SCORE_ENTRIES: usize = 10;
+pub const HIGH_SCORE_FILE: &str = "highscores.ron";
+const NAME_LETTERS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789.-";
+const NAME_LENGTH: usize = 3;
+
+// keeps the top scores on disk, left out for replays and headless runs so they never write the table
+pub struct HighScorePlugin;
+
+impl Plugin for HighScorePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, high_score_load_system)
+            .add_systems(OnEnter(GameState::NameEntry), name_entry_setup_system)
+            .add_systems(Update, name_entry_input_system.run_if(in_state(GameState::NameEntry)))
+            .add_systems(OnExit(GameState::NameEntry), despawn_screen::<OnNameEntryScreen>);
+    }
+}
+
+// cheat runs get their own table
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameMode {
+    Arcade,
+    Cheat,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Difficulty {
+    Normal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HighScoreEntry {
+    pub name: String,
+    pub score: u32,
+}
+
+// best first, at most HIGH_SCORE_ENTRIES long
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HighScoreTable {
+    pub mode: GameMode,
+    pub difficulty: Difficulty,
+    pub entries: Vec<HighScoreEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HighScoreFile {
+    pub version: u32,
+    pub tables: Vec<HighScoreTable>,
+}
+
+#[derive(Deserialize)]
+struct HighScoreHeader {
+    version: u32,
+}
+
+#[derive(Debug, Error)]
+pub enum HighScoreError {
+    #[error("could not access high score file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse high score file: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("could not write high score file: {0}")]
+    Write(#[from] ron::Error),
+    #[error("unsupported high score version {0}, expected {HIGH_SCORE_VERSION}")]
+    Version(u32),
+}
+
+impl Default for HighScoreFile {
+    fn default() -> Self {
+        Self { version: HIGH_SCORE_VERSION, tables: Vec::new() }
+    }
+}
+
+impl HighScoreFile {
+    // a missing file is an empty table
+    pub fn load(path: &Path) -> Result<Self, HighScoreError> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(error) => return Err(error.into()),
+        };
+
+        // check the version before the fields it decides
+        let header: HighScoreHeader = ron::from_str(&text)?;
+        if header.version != HIGH_SCORE_VERSION {
+            return Err(HighScoreError::Version(header.version));
+        }
+        Ok(ron::from_str(&text)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), HighScoreError> {
+        write_atomic(path, &ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?)?;
+        Ok(())
+    }
+}
+
+#[derive(Resource)]
+pub struct HighScores {
+    path: PathBuf,
+    file: HighScoreFile,
+    mode: GameMode,
+    difficulty: Difficulty,
+}
+
+impl HighScores {
+    pub fn entries(&self) -> &[HighScoreEntry] {
+        self.file.tables.iter()
+            .find(|table| table.mode == self.mode && table.difficulty == self.difficulty)
+            .map_or(&[], |table| &table.entries)
+    }
+
+    pub fn best(&self) -> u32 {
+        self.entries().first().map_or(0, |entry| entry.score)
+    }
+
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > 0 && (self.entries().len() < HIGH_SCORE_ENTRIES || self.entries().iter().any(|entry| score > entry.score))
+    }
+
+    // ties keep the older entry first
+    pub fn insert(&mut self, name: String, score: u32) {
+        let (mode, difficulty) = (self.mode, self.difficulty);
+        let index = match self.file.tables.iter().position(|table| table.mode == mode && table.difficulty == difficulty) {
+            Some(index) => index,
+            None => {
+                self.file.tables.push(HighScoreTable { mode, difficulty, entries: Vec::new() });
+                self.file.tables.len() - 1
+            }
+        };
+
+        let entries = &mut self.file.tables[index].entries;
+        let rank = entries.iter().position(|entry| score > entry.score).unwrap_or(entries.len());
+        entries.insert(rank, HighScoreEntry { name, score });
+        entries.truncate(HIGH_SCORE_ENTRIES);
+    }
+
+    pub fn save(&self) -> Result<(), HighScoreError> {
+        self.file.save(&self.path)
+    }
+}
+
+fn high_score_load_system(
+    mut commands: Commands,
+    cheats: Option<Res<Cheats>>,
+) {
+    let path = data_path(HIGH_SCORE_FILE);
+    let file = HighScoreFile::load(&path).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        HighScoreFile::default()
+    });
+
+    commands.insert_resource(HighScores {
+        path,
+        file,
+        mode: if cheats.is_some() { GameMode::Cheat } else { GameMode::Arcade },
+        difficulty: Difficulty::Normal,
+    });
+}
+
+// letters picked so far, the last one is under the cursor
+#[derive(Component)]
+struct NameEntry {
+    letters: Vec<usize>,
+}
+
+impl NameEntry {
+    fn name(&self) -> String {
+        self.letters.iter().map(|letter| NAME_LETTERS[*letter] as char).collect()
+    }
+}
+
+fn name_entry_setup_system(
+    mut commands: Commands,
+    player_status: Res<PlayerStatus>,
+    key_bindings: Res<KeyBindings>,
+) {
+    commands.spawn((screen_node(), OnNameEntryScreen)).with_children(|p| {
+        p.spawn(screen_text("new high score!", 40.0));
+        p.spawn(screen_text(format!("score: {:04}", player_status.score as u32), 20.0));
+        p.spawn((screen_text("A", 40.0), NameEntry { letters: vec![0] }));
+        p.spawn(screen_text(format!(
+            "{}/{}: letter / {}: next / {}: back",
+            key_bindings.name(GameButton::Up),
+            key_bindings.name(GameButton::Down),
+            key_bindings.name(GameButton::Shoot),
+            key_bindings.name(GameButton::Cancel),
+        ), 20.0));
+    });
+}
+
+fn name_entry_input_system(
+    input: Res<GameInput>,
+    mut query: Query<(&mut Text, &mut NameEntry)>,
+    mut high_scores: ResMut<HighScores>,
+    player_status: Res<PlayerStatus>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Ok((mut text, mut name_entry)) = query.get_single_mut() else {
+        return;
+    };
+
+    let cursor = name_entry.letters.len() - 1;
+    if input.just_pressed(GameButton::Up) {
+        name_entry.letters[cursor] = (name_entry.letters[cursor] + 1) % NAME_LETTERS.len();
+    } else if input.just_pressed(GameButton::Down) {
+        name_entry.letters[cursor] = (name_entry.letters[cursor] + NAME_LETTERS.len() - 1) % NAME_LETTERS.len();
+    } else if input.just_pressed(GameButton::Cancel) && cursor > 0 {
+        name_entry.letters.pop();
+    } else if input.just_pressed(GameButton::Shoot) {
+        if name_entry.letters.len() < NAME_LENGTH {
+            // the next letter starts where the last one was left
+            let letter = name_entry.letters[cursor];
+            name_entry.letters.push(letter);
+        } else {
+            high_scores.insert(name_entry.name(), player_status.score as u32);
+            if let Err(error) = high_scores.save() {
+                eprintln!("{}", error);
+            }
+            next_state.set(GameState::Results);
+        }
+    }
+
+    text.sections[0].value = name_entry.name();
+}
+
+// where a finished run goes after the game over screen
+pub fn run_end_state(high_scores: Option<&HighScores>, player_status: &PlayerStatus) -> GameState {
+    match high_scores {
+        Some(high_scores) if high_scores.qualifies(player_status.score as u32) => GameState::NameEntry,
+        _ => GameState::Results,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn high_scores(file: HighScoreFile, mode: GameMode) -> HighScores {
+        let path = std::env::temp_dir().join(format!("shoot-game-highscores-{}-{:?}.ron", std::process::id(), mode));
+        HighScores { path, file, mode, difficulty: Difficulty::Normal }
+    }
+
+    fn scores(high_scores: &HighScores) -> Vec<u32> {
+        high_scores.entries().iter().map(|entry| entry.score).collect()
+    }
+
+    #[test]
+    fn table_keeps_the_top_ten_best_first() {
+        let mut high_scores = high_scores(HighScoreFile::default(), GameMode::Arcade);
+        assert!(!high_scores.qualifies(0));
+        for score in [30, 10, 50, 20, 40, 60, 70, 80, 90, 100] {
+            assert!(high_scores.qualifies(score));
+            high_scores.insert(format!("{}", score), score);
+        }
+        assert_eq!(scores(&high_scores), vec![100, 90, 80, 70, 60, 50, 40, 30, 20, 10]);
+        assert_eq!(high_scores.best(), 100);
+
+        // a full table takes only what beats the last entry
+        assert!(!high_scores.qualifies(10));
+        assert!(high_scores.qualifies(11));
+        high_scores.insert("NEW".to_string(), 55);
+        assert_eq!(scores(&high_scores), vec![100, 90, 80, 70, 60, 55, 50, 40, 30, 20]);
+
+        // ties keep the older entry first
+        high_scores.insert("TIE".to_string(), 90);
+        assert_eq!(high_scores.entries()[1].name, "90");
+        assert_eq!(high_scores.entries()[2].name, "TIE");
+        assert_eq!(high_scores.entries().len(), HIGH_SCORE_ENTRIES);
+    }
+
+    #[test]
+    fn modes_keep_their_own_tables() {
+        let mut arcade = high_scores(HighScoreFile::default(), GameMode::Arcade);
+        arcade.insert("ARC".to_string(), 500);
+
+        // a cheat run on the same file neither sees nor touches the arcade table
+        let mut cheat = high_scores(arcade.file.clone(), GameMode::Cheat);
+        assert_eq!(cheat.best(), 0);
+        cheat.insert("CHT".to_string(), 100);
+        assert_eq!(scores(&cheat), vec![100]);
+        assert_eq!(cheat.file.tables.len(), 2);
+
+        let arcade = high_scores(cheat.file.clone(), GameMode::Arcade);
+        assert_eq!(scores(&arcade), vec![500]);
+        assert!(cheat.file.tables.iter().all(|table| table.difficulty == Difficulty::Normal));
+    }
+
+    #[test]
+    fn table_survives_a_save() {
+        let mut high_scores = high_scores(HighScoreFile::default(), GameMode::Arcade);
+        high_scores.insert("ABC".to_string(), 1234);
+        high_scores.save().unwrap();
+
+        let file = HighScoreFile::load(&high_scores.path).unwrap();
+        fs::remove_file(&high_scores.path).unwrap();
+        let loaded = self::high_scores(file, GameMode::Arcade);
+        assert_eq!(loaded.entries()[0].name, "ABC");
+        assert_eq!(loaded.best(), 1234);
+    }
+
+    #[test]
+    fn qualifying_runs_go_to_name_entry() {
+        let mut high_scores = high_scores(HighScoreFile::default(), GameMode::Arcade);
+        let player_status = PlayerStatus { score: 10.0, ..PlayerStatus::default() };
+        assert_eq!(run_end_state(Some(&high_scores), &player_status), GameState::NameEntry);
+        assert_eq!(run_end_state(None, &player_status), GameState::Results);
+
+        for _ in 0..HIGH_SCORE_ENTRIES {
+            high_scores.insert("TOP".to_string(), 100);
+        }
+        assert_eq!(run_end_state(Some(&high_scores), &player_status), GameState::Results);
+    }
+}
@@ -0,0 +1,357 @@
+use bevy::{prelude::*, input::{InputSystem, gamepad::GamepadConnectionEvent}};
+use serde::{Deserialize, Serialize};
+
+use crate::{define::{GameState, GameSet, GAMEPAD_DEADZONE, GAMEPAD_STICK_PRESS}, replay::ReplayPlayback};
+
+pub struct GameInputPlugin;
+
+impl Plugin for GameInputPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<GameInput>()
+            .init_resource::<KeyBindings>()
+            .add_systems(PreUpdate, device_input_system
+                .after(InputSystem)
+                .run_if(not(resource_exists::<ReplayPlayback>()))
+                .run_if(not(in_state(GameState::Loading))))
+            .add_systems(FixedUpdate, game_input_tick_system.after(GameSet::Item));
+    }
+}
+
+// the actions game systems read, named the same in the settings file
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameButton {
+    Up,
+    Down,
+    Left,
+    Right,
+    Shoot,
+    ShootNormal,
+    ShootDouble,
+    ShootTriple,
+    Pause,
+    SpeedUp,
+    SpeedDown,
+    Cancel,
+    Bomb,
+    Focus,
+    Quit,
+}
+
+impl GameButton {
+    pub const ALL: [GameButton; 15] = [
+        GameButton::Up,
+        GameButton::Down,
+        GameButton::Left,
+        GameButton::Right,
+        GameButton::Shoot,
+        GameButton::ShootNormal,
+        GameButton::ShootDouble,
+        GameButton::ShootTriple,
+        GameButton::Pause,
+        GameButton::SpeedUp,
+        GameButton::SpeedDown,
+        GameButton::Cancel,
+        GameButton::Bomb,
+        GameButton::Focus,
+        GameButton::Quit,
+    ];
+
+    fn bit(self) -> u16 {
+        1 << self as u16
+    }
+
+    fn default_key(self) -> KeyCode {
+        match self {
+            GameButton::Up => KeyCode::Up,
+            GameButton::Down => KeyCode::Down,
+            GameButton::Left => KeyCode::Left,
+            GameButton::Right => KeyCode::Right,
+            GameButton::Shoot => KeyCode::Space,
+            GameButton::ShootNormal => KeyCode::N,
+            GameButton::ShootDouble => KeyCode::D,
+            GameButton::ShootTriple => KeyCode::T,
+            GameButton::Pause => KeyCode::P,
+            GameButton::SpeedUp => KeyCode::A,
+            GameButton::SpeedDown => KeyCode::Z,
+            GameButton::Cancel => KeyCode::Q,
+            GameButton::Bomb => KeyCode::X,
+            GameButton::Focus => KeyCode::ShiftLeft,
+            GameButton::Quit => KeyCode::Escape,
+        }
+    }
+
+    // every connected pad uses the same layout
+    fn pad_button(self) -> Option<GamepadButtonType> {
+        match self {
+            GameButton::Up => Some(GamepadButtonType::DPadUp),
+            GameButton::Down => Some(GamepadButtonType::DPadDown),
+            GameButton::Left => Some(GamepadButtonType::DPadLeft),
+            GameButton::Right => Some(GamepadButtonType::DPadRight),
+            GameButton::Shoot => Some(GamepadButtonType::South),
+            GameButton::Bomb => Some(GamepadButtonType::East),
+            GameButton::Cancel => Some(GamepadButtonType::West),
+            GameButton::Focus => Some(GamepadButtonType::RightTrigger),
+            GameButton::Pause => Some(GamepadButtonType::Start),
+            _ => None,
+        }
+    }
+}
+
+// one key per button, loaded from the settings file
+#[derive(Resource, Clone, PartialEq)]
+pub struct KeyBindings {
+    keys: [KeyCode; GameButton::ALL.len()],
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self { keys: GameButton::ALL.map(GameButton::default_key) }
+    }
+}
+
+impl KeyBindings {
+    pub fn key(&self, button: GameButton) -> KeyCode {
+        self.keys[button as usize]
+    }
+
+    pub fn bind(&mut self, button: GameButton, key: KeyCode) {
+        self.keys[button as usize] = key;
+    }
+
+    // the other button already on this key
+    pub fn conflict(&self, button: GameButton, key: KeyCode) -> Option<GameButton> {
+        GameButton::ALL.iter().copied().find(|other| *other != button && self.key(*other) == key)
+    }
+
+    // for on-screen prompts, e.g. SPACE
+    pub fn name(&self, button: GameButton) -> String {
+        format!("{:?}", self.key(button)).to_uppercase()
+    }
+}
+
+// buttons held this frame, game systems read this instead of the keyboard
+#[derive(Resource, Default)]
+pub struct GameInput {
+    buttons: u16,
+    previous: u16,
+    // presses not yet seen by a fixed tick, a frame can run zero or several ticks
+    latched: u16,
+    // analog stick past the deadzone, -127..=127 per axis so replays store it exactly
+    stick: [i8; 2],
+}
+
+impl GameInput {
+    pub fn buttons(&self) -> u16 {
+        self.buttons
+    }
+
+    pub fn set_buttons(&mut self, buttons: u16) {
+        self.previous = self.buttons;
+        self.buttons = buttons;
+        self.latched |= self.buttons & !self.previous;
+    }
+
+    pub fn pressed(&self, button: GameButton) -> bool {
+        self.buttons & button.bit() != 0
+    }
+
+    pub fn just_pressed(&self, button: GameButton) -> bool {
+        self.pressed(button) && self.previous & button.bit() == 0
+    }
+
+    // just_pressed for FixedUpdate systems, each press is seen by exactly one tick
+    pub fn tick_just_pressed(&self, button: GameButton) -> bool {
+        self.latched & button.bit() != 0
+    }
+
+    pub fn stick(&self) -> [i8; 2] {
+        self.stick
+    }
+
+    pub fn set_stick(&mut self, stick: [i8; 2]) {
+        self.stick = stick;
+    }
+
+    // the stick when it is pushed, otherwise full speed on each held direction
+    pub fn movement(&self) -> Vec2 {
+        if self.stick != [0, 0] {
+            return Vec2::new(self.stick[0] as f32, self.stick[1] as f32) / i8::MAX as f32;
+        }
+        let axis = |negative, positive| self.pressed(positive) as i32 as f32 - self.pressed(negative) as i32 as f32;
+        Vec2::new(axis(GameButton::Left, GameButton::Right), axis(GameButton::Down, GameButton::Up))
+    }
+}
+
+// radial, rescaled so the stick starts from zero at the edge of the deadzone
+fn stick_deadzone(stick: Vec2) -> Vec2 {
+    let length = stick.length();
+    if length <= GAMEPAD_DEADZONE {
+        return Vec2::ZERO;
+    }
+    stick / length * (length.min(1.0) - GAMEPAD_DEADZONE) / (1.0 - GAMEPAD_DEADZONE)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn device_input_system(
+    input: Res<Input<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    gamepads: Res<Gamepads>,
+    pad_buttons: Res<Input<GamepadButton>>,
+    pad_axes: Res<Axis<GamepadAxis>>,
+    mut connection_events: EventReader<GamepadConnectionEvent>,
+    state: Res<State<GameState>>,
+    mut game_input: ResMut<GameInput>,
+) {
+    let mut buttons = GameButton::ALL.iter()
+        .filter(|button| input.pressed(key_bindings.key(**button)))
+        .fold(0, |buttons, button| buttons | button.bit());
+
+    let mut stick = Vec2::ZERO;
+    for gamepad in gamepads.iter() {
+        for button in GameButton::ALL {
+            if button.pad_button().is_some_and(|pad_button| pad_buttons.pressed(GamepadButton::new(gamepad, pad_button))) {
+                buttons |= button.bit();
+            }
+        }
+        stick += Vec2::new(
+            pad_axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX)).unwrap_or(0.0),
+            pad_axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY)).unwrap_or(0.0),
+        );
+    }
+    let stick = stick_deadzone(stick);
+
+    // pushed far enough the stick also counts as the direction buttons, so menus follow it
+    for (value, negative, positive) in [(stick.x, GameButton::Left, GameButton::Right), (stick.y, GameButton::Down, GameButton::Up)] {
+        if value < -GAMEPAD_STICK_PRESS {
+            buttons |= negative.bit();
+        } else if value > GAMEPAD_STICK_PRESS {
+            buttons |= positive.bit();
+        }
+    }
+
+    // a pad coming or going pauses the game, pressed like any button so replays keep it
+    if connection_events.read().count() > 0 && *state.get() == GameState::InGame {
+        buttons |= GameButton::Pause.bit();
+    }
+
+    game_input.set_buttons(buttons);
+    game_input.set_stick([
+        (stick.x * i8::MAX as f32).round() as i8,
+        (stick.y * i8::MAX as f32).round() as i8,
+    ]);
+}
+
+fn game_input_tick_system(
+    mut game_input: ResMut<GameInput>,
+) {
+    game_input.latched = 0;
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::{InputPlugin, gamepad::{GamepadConnection, GamepadInfo, GamepadAxisChangedEvent, GamepadButtonChangedEvent}};
+
+    use super::*;
+
+    const PAD: Gamepad = Gamepad { id: 0 };
+
+    // just the input plugins, already past loading
+    fn input_app(state: GameState) -> App {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, InputPlugin, GameInputPlugin))
+            .add_state::<GameState>();
+        app.world.resource_mut::<NextState<GameState>>().set(state);
+        app.update();
+        app
+    }
+
+    fn connection(app: &mut App, connection: GamepadConnection) {
+        app.world.send_event(GamepadConnectionEvent::new(PAD, connection));
+        app.update();
+    }
+
+    fn connected() -> GamepadConnection {
+        GamepadConnection::Connected(GamepadInfo { name: "pad".to_string() })
+    }
+
+    fn game_input(app: &App) -> &GameInput {
+        app.world.resource::<GameInput>()
+    }
+
+    #[test]
+    fn deadzone_rescales_from_its_edge() {
+        assert_eq!(stick_deadzone(Vec2::new(0.1, 0.1)), Vec2::ZERO);
+        assert_eq!(stick_deadzone(Vec2::new(0.0, GAMEPAD_DEADZONE)), Vec2::ZERO);
+        assert!(stick_deadzone(Vec2::new(GAMEPAD_DEADZONE + 0.01, 0.0)).x < 0.02);
+        assert!((stick_deadzone(Vec2::new(0.6, 0.0)) - Vec2::new(0.5, 0.0)).length() < 1e-5);
+        assert!((stick_deadzone(Vec2::new(1.0, 0.0)) - Vec2::X).length() < 1e-5);
+
+        // direction kept, length capped at full
+        let diagonal = stick_deadzone(Vec2::new(1.0, 1.0));
+        assert!((diagonal.length() - 1.0).abs() < 1e-5);
+        assert!((diagonal.x - diagonal.y).abs() < 1e-5);
+    }
+
+    #[test]
+    fn connection_change_presses_pause_in_game() {
+        let mut app = input_app(GameState::InGame);
+        connection(&mut app, connected());
+        assert!(game_input(&app).just_pressed(GameButton::Pause));
+        app.update();
+        assert!(!game_input(&app).pressed(GameButton::Pause));
+
+        connection(&mut app, GamepadConnection::Disconnected);
+        assert!(game_input(&app).just_pressed(GameButton::Pause));
+    }
+
+    #[test]
+    fn connection_change_outside_game_does_not_pause() {
+        let mut app = input_app(GameState::Title);
+        connection(&mut app, connected());
+        assert!(!game_input(&app).pressed(GameButton::Pause));
+    }
+
+    #[test]
+    fn pad_buttons_and_stick() {
+        let mut app = input_app(GameState::Title);
+        connection(&mut app, connected());
+
+        app.world.send_event(GamepadButtonChangedEvent::new(PAD, GamepadButtonType::South, 1.0));
+        app.update();
+        assert!(game_input(&app).just_pressed(GameButton::Shoot));
+
+        // a light push moves slowly without counting as the direction button
+        app.world.send_event(GamepadAxisChangedEvent::new(PAD, GamepadAxisType::LeftStickX, 0.4));
+        app.update();
+        assert_eq!(game_input(&app).stick(), [32, 0]);
+        assert!((game_input(&app).movement() - Vec2::new(32.0 / 127.0, 0.0)).length() < 1e-5);
+        assert!(!game_input(&app).pressed(GameButton::Right));
+
+        app.world.send_event(GamepadAxisChangedEvent::new(PAD, GamepadAxisType::LeftStickX, 0.0));
+        app.world.send_event(GamepadAxisChangedEvent::new(PAD, GamepadAxisType::LeftStickY, -1.0));
+        app.update();
+        assert_eq!(game_input(&app).stick(), [0, -127]);
+        assert_eq!(game_input(&app).movement(), Vec2::NEG_Y);
+        assert!(game_input(&app).pressed(GameButton::Down));
+
+        // the stick is let go once the pad is gone
+        connection(&mut app, GamepadConnection::Disconnected);
+        assert_eq!(game_input(&app).stick(), [0, 0]);
+        assert!(!game_input(&app).pressed(GameButton::Shoot));
+    }
+
+    #[test]
+    fn conflict_names_the_other_button() {
+        let mut key_bindings = KeyBindings::default();
+        assert_eq!(key_bindings.conflict(GameButton::Bomb, GameButton::Shoot.default_key()), Some(GameButton::Shoot));
+        // a button keeping its own key is not a conflict
+        assert_eq!(key_bindings.conflict(GameButton::Shoot, GameButton::Shoot.default_key()), None);
+        assert_eq!(key_bindings.conflict(GameButton::Shoot, KeyCode::F12), None);
+
+        key_bindings.bind(GameButton::Shoot, KeyCode::F12);
+        assert_eq!(key_bindings.conflict(GameButton::Bomb, GameButton::Shoot.default_key()), None);
+        assert_eq!(key_bindings.conflict(GameButton::Bomb, KeyCode::F12), Some(GameButton::Shoot));
+    }
+}
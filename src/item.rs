@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{define::*, collision::hitbox_intersects, enemy::EnemyDefeatedEvent, rng::{GameRng, RngStream}, sound::SoundEvent};
+
+pub struct ItemPlugin;
+
+impl Plugin for ItemPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, (
+            item_drop_system,
+            item_pickup_system,
+        ).chain().in_set(GameSet::Item).run_if(in_state(GameState::InGame)));
+    }
+}
+
+fn item_drop_system(
+    mut commands: Commands,
+    mut defeated_events: EventReader<EnemyDefeatedEvent>,
+    mut game_rng: ResMut<GameRng>,
+) {
+    for event in defeated_events.read() {
+        // always roll, so one enemy's odds never shift the next enemy's result
+        let roll: f64 = game_rng.stream(RngStream::Drop).gen();
+        if roll >= event.drop_chance {
+            continue;
+        }
+
+        commands.spawn((
+            SpatialBundle::from_transform(Transform::from_xyz(event.x, event.y, 5.0)),
+            Velocity { x: 0.0, y: -ITEM_VELOCITY },
+            Hitbox::circle(ITEM_RADIUS),
+            AutoDespawn,
+            PowerItem,
+            OnGameScreen,
+        ));
+    }
+}
+
+// picked up by touching the ship, not just its small hitbox
+fn item_pickup_system(
+    mut commands: Commands,
+    player: Query<(&Transform, &Player)>,
+    items: Query<(Entity, &Transform, &Hitbox), With<PowerItem>>,
+    mut player_status: ResMut<PlayerStatus>,
+    mut sound_events: EventWriter<SoundEvent>,
+) {
+    let Ok((player_transform, player)) = player.get_single() else {
+        return;
+    };
+    if !player.is_enable {
+        return;
+    }
+
+    let reach = Hitbox::circle(PLAYER_RADIUS);
+    for (entity, transform, hitbox) in items.iter() {
+        if hitbox_intersects(&reach, player_transform.translation.truncate(), hitbox, transform.translation.truncate()) {
+            commands.entity(entity).despawn();
+            player_status.power += 1;
+            sound_events.send(SoundEvent::ItemPickup);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEFEATS: usize = 200;
+
+    fn drop_app(seed: u64, drop_chance: f64) -> App {
+        let mut app = App::new();
+        app
+            .add_event::<EnemyDefeatedEvent>()
+            .insert_resource(GameRng::new(seed))
+            .add_systems(Update, item_drop_system);
+        for _ in 0..DEFEATS {
+            app.world.send_event(EnemyDefeatedEvent { x: 0.0, y: 0.0, drop_chance });
+        }
+        app.update();
+        app
+    }
+
+    fn items(app: &mut App) -> usize {
+        app.world.query_filtered::<(), With<PowerItem>>().iter(&app.world).count()
+    }
+
+    #[test]
+    fn drops_follow_the_drop_stream() {
+        assert_eq!(items(&mut drop_app(5, 0.0)), 0);
+        assert_eq!(items(&mut drop_app(5, 1.0)), DEFEATS);
+
+        // one roll from the Drop stream per defeat, whatever the odds
+        let mut game_rng = GameRng::new(5);
+        let expected = (0..DEFEATS).filter(|_| game_rng.stream(RngStream::Drop).gen::<f64>() < 0.3).count();
+        let mut app = drop_app(5, 0.3);
+        assert_eq!(items(&mut app), expected);
+        assert!(expected > 0 && expected < DEFEATS);
+
+        // and no other stream moves
+        let fresh: f32 = GameRng::new(5).stream(RngStream::EnemyFire).gen();
+        let used: f32 = app.world.resource_mut::<GameRng>().stream(RngStream::EnemyFire).gen();
+        assert_eq!(used, fresh);
+    }
+}
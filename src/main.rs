@@ -1,18 +1,87 @@
-use bevy::{prelude::*, window::WindowResolution};
+use std::time::Duration;
+
+use bevy::{prelude::*, app::{AppExit, ScheduleRunnerPlugin}, input::InputPlugin, time::TimeUpdateStrategy, window::WindowResolution};
 use define::*;
 use player::PlayerPlugin;
 use enemy::EnemyPlugin;
 use collision::CollisionPlugin;
 use common::CommonPlugin;
+use screen::{ScreenPlugin, despawn_screen};
+use stage::StagePlugin;
+use visual::VisualPlugin;
+use rng::{GameRng, GameSeed};
+use input::{GameInputPlugin, GameInput, GameButton};
+use replay::{ReplayPlugin, ReplayMode};
+use bench::BenchPlugin;
+use item::ItemPlugin;
+use bomb::BombPlugin;
+use highscore::{HighScorePlugin, HighScores};
+use settings::SettingsPlugin;
+use sound::{SoundPlugin, SoundEvent};
+use particle::{ParticlePlugin, ParticleEvent};
 
 mod player;
 mod enemy;
 mod define;
 mod collision;
 mod common;
+mod screen;
+mod stage;
+mod visual;
+mod rng;
+mod input;
+mod replay;
+mod bench;
+mod item;
+mod bomb;
+mod highscore;
+mod settings;
+mod sound;
+mod particle;
 
 fn main() {
-    App::new()
+    let replay = match ReplayMode::from_args() {
+        Ok(replay) => replay,
+        Err(error) => {
+            eprintln!("{}", error);
+            return;
+        }
+    };
+
+    // a replay brings its own seed
+    let seed = match &replay {
+        ReplayMode::Playback(replay) => GameSeed(replay.seed),
+        _ => GameSeed::from_args(),
+    };
+    if is_verbose() {
+        println!("seed: {}", seed.0);
+    }
+
+    // --bench <bullets> measures a crowded playfield without a window
+    if let Some(bullets) = arg_value("--bench").and_then(|value| value.parse().ok()) {
+        let mut app = headless_app(Vec2::new(WINDOW_SIZE_WIDTH, WINDOW_SIZE_HEIGHT), seed, ReplayMode::Off);
+        app.add_plugins(BenchPlugin { bullets });
+        app.run();
+    } else if std::env::args().any(|arg| arg == "--headless") {
+        headless_app(Vec2::new(WINDOW_SIZE_WIDTH, WINDOW_SIZE_HEIGHT), seed, replay).run();
+    } else {
+        window_app(seed, replay).run();
+    }
+}
+
+// value following a command-line flag, e.g. --seed 42
+pub fn arg_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
+}
+
+// --verbose prints the seed, speed changes and the headless run's result
+pub fn is_verbose() -> bool {
+    std::env::args().any(|arg| arg == "--verbose")
+}
+
+pub fn window_app(seed: GameSeed, replay: ReplayMode) -> App {
+    let mut app = App::new();
+    app
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
                 title: "shooting game!!!".to_string(),
@@ -22,54 +91,123 @@ fn main() {
             }),
             ..default()
         }))
+        .add_plugins((VisualPlugin, SoundPlugin, ParticlePlugin));
+
+    // a replay would enter its recorded name again
+    if !matches!(replay, ReplayMode::Playback(_)) {
+        app.add_plugins(HighScorePlugin);
+    }
+
+    let skip_title = replay.skip_title(false);
+    add_game(&mut app, Vec2::new(WINDOW_SIZE_WIDTH, WINDOW_SIZE_HEIGHT), seed, replay, skip_title);
+    app
+}
+
+// game logic only, no window or GPU. every update advances a fixed frame
+pub fn headless_app(playfield: Vec2, seed: GameSeed, replay: ReplayMode) -> App {
+    let mut app = App::new();
+    app
+        .add_plugins((
+            MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::ZERO)),
+            AssetPlugin::default(),
+            InputPlugin,
+        ))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(HEADLESS_FRAME_SECONDS)));
+
+    // skip the title screen and stop when the run is over
+    let skip_title = replay.skip_title(true);
+    add_game(&mut app, playfield, seed, replay, skip_title);
+    app.add_systems(OnEnter(GameState::Results), headless_exit_system);
+    app
+}
+
+fn add_game(app: &mut App, playfield: Vec2, seed: GameSeed, replay: ReplayMode, skip_title: bool) {
+    let cheats = replay.cheats(std::env::args().any(|arg| arg == "--cheat"));
+    if cheats {
+        app.insert_resource(Cheats);
+    }
+
+    app
+        .add_state::<GameState>()
+        .insert_resource(WindowSizeLimit::from_size(playfield.x, playfield.y))
+        .insert_resource(seed)
+        .add_event::<SoundEvent>()
+        .add_event::<ParticleEvent>()
+        .add_plugins((
+            GameInputPlugin,
+            ReplayPlugin { mode: replay, skip_title, cheats },
+        ))
         .add_plugins((
             PlayerPlugin,
             EnemyPlugin,
             CollisionPlugin,
             CommonPlugin,
+            ScreenPlugin,
+            StagePlugin,
+            ItemPlugin,
+            BombPlugin,
+            SettingsPlugin,
         ))
         .add_systems(Startup, setup_system)
+        .add_systems(OnTransition { from: GameState::Title, to: GameState::InGame }, game_setup_system)
+        .add_systems(OnEnter(GameState::Results), despawn_screen::<OnGameScreen>)
+        .insert_resource(Time::<Fixed>::from_hz(FIXED_UPDATE_HZ))
+        .configure_sets(FixedUpdate, (
+            GameSet::Timer,
+            GameSet::Enemy,
+            GameSet::Player,
+            GameSet::Bomb,
+            GameSet::Common,
+            GameSet::Collision,
+            GameSet::Item,
+        ).chain())
+        .add_systems(FixedUpdate, game_timer_system.in_set(GameSet::Timer).run_if(in_state(GameState::InGame)))
         .add_systems(Update, (
-            game_timer_system,
             update_score_board,
-            toggle_pause,
-            bevy::window::close_on_esc
-        ))
-        .run();
+            update_lives_board,
+        ).run_if(in_state(GameState::InGame)))
+        .add_systems(Update, (
+            toggle_pause.run_if(in_state(GameState::InGame).or_else(in_state(GameState::Paused))),
+            quit_system,
+        ));
+
+    if skip_title {
+        app.insert_resource(SkipTitle);
+    }
 }
 
 fn setup_system(
-    mut commands: Commands,
-    query: Query<&Window>,
     mut time: ResMut<Time<Virtual>>,
 ) {
     time.set_relative_speed(1.5);
+}
 
-    // camera
-    commands.spawn(Camera2dBundle::default());
-
-    // get window limit
-    let window = query.single();
-    let width = window.resolution.width() / 2.0;
-    let height = window.resolution.height() / 2.0;
+fn headless_exit_system(
+    player_status: Res<PlayerStatus>,
+    game_timer: Res<GameTimer>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if is_verbose() {
+        println!("score: {}, graze: {}, time: {}s", player_status.score, player_status.graze, game_timer.seconds);
+    }
+    exit.send(AppExit);
+}
 
-    commands.insert_resource(WindowSizeLimit::new(height, -height, width, -width));
+fn game_setup_system(
+    mut commands: Commands,
+    seed: Res<GameSeed>,
+    mut sound_events: EventWriter<SoundEvent>,
+) {
+    sound_events.send(SoundEvent::StageStart);
 
-    // enemy count
-    commands.insert_resource(EnemySpawn {
-        counter: 0,
-        timer: Timer::from_seconds(ENEMY_SPAWN_DURATION_SECONDS, TimerMode::Repeating),
-    });
+    // same seed, same run
+    commands.insert_resource(GameRng::new(seed.0));
 
     // speed control
     commands.insert_resource(SpeedControl { value: 1.0 });
 
     // player spawn
-    commands.insert_resource(PlayerStatus {
-        is_spawn: true,
-        spawn_timer: Timer::from_seconds(1.0, TimerMode::Once),
-        score: 0.0,
-    });
+    commands.insert_resource(PlayerStatus::default());
 
     // set game timer
     commands.insert_resource(GameTimer {
@@ -77,21 +215,24 @@ fn setup_system(
         seconds: 0,
     });
 
-    commands.spawn(NodeBundle {
-        style: Style {
-            display: Display::Flex,
-            justify_content: JustifyContent::SpaceBetween,
-            width: Val::Percent(100.),
-            position_type: PositionType::Absolute,
-            top: Val::Px(0.),
-            padding: UiRect::all(Val::Px(10.0)),
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                display: Display::Flex,
+                justify_content: JustifyContent::SpaceBetween,
+                width: Val::Percent(100.),
+                position_type: PositionType::Absolute,
+                top: Val::Px(0.),
+                padding: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
             ..default()
         },
-        ..default()
-    }).with_children(|p| {
+        OnGameScreen,
+    )).with_children(|p| {
         p.spawn((
             TextBundle::from_section(
-                "score:0000",
+                "score:0000 x1",
                 TextStyle {
                     font_size: 20.0,
                     ..default()
@@ -99,12 +240,22 @@ fn setup_system(
             ),
             ScoreBoard,
         ));
+        p.spawn((
+            TextBundle::from_section(
+                "lives:3 bombs:3 power:0 graze:0",
+                TextStyle {
+                    font_size: 20.0,
+                    ..default()
+                },
+            ),
+            LivesBoard,
+        ));
     });
 }
 
 fn game_timer_system(
     mut game_timer: ResMut<GameTimer>,
-    time: Res<Time<Virtual>>,
+    time: Res<Time>,
 ) {
     if game_timer.timer.tick(time.delta()).just_finished() {
         game_timer.seconds += 1;
@@ -113,22 +264,88 @@ fn game_timer_system(
 
 fn update_score_board(
     player_status: Res<PlayerStatus>,
+    high_scores: Option<Res<HighScores>>,
     mut query: Query<&mut Text, With<ScoreBoard>>,
+) {
+    let score = player_status.score as u32;
+    for mut text in query.iter_mut() {
+        text.sections[0].value = match &high_scores {
+            Some(high_scores) => format!("score: {:04} x{} hi: {:04}", score, player_status.chain_multiplier(), high_scores.best().max(score)),
+            None => format!("score: {:04} x{}", score, player_status.chain_multiplier()),
+        };
+    }
+}
+
+fn update_lives_board(
+    player_status: Res<PlayerStatus>,
+    mut query: Query<&mut Text, With<LivesBoard>>,
 ) {
     for mut text in query.iter_mut() {
-        text.sections[0].value = format!("score: {:04}", player_status.score as u32);
+        text.sections[0].value = format!("lives: {} bombs: {} power: {} graze: {}", player_status.lives, player_status.bombs, player_status.power, player_status.graze_meter);
     }
 }
 
 fn toggle_pause(
-    mut time: ResMut<Time<Virtual>>,
-    input: Res<Input<KeyCode>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    input: Res<GameInput>,
 ) {
-    if input.just_pressed(KeyCode::P) {
-        if time.is_paused() {
-            time.unpause();
-        } else {
-            time.pause();
+    if input.just_pressed(GameButton::Pause) {
+        match state.get() {
+            GameState::InGame => next_state.set(GameState::Paused),
+            GameState::Paused => next_state.set(GameState::InGame),
+            _ => {}
+        }
+    }
+}
+
+fn quit_system(
+    input: Res<GameInput>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if input.just_pressed(GameButton::Quit) {
+        exit.send(AppExit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // stage length plus the boss and a continue countdown, with room to spare
+    const STAGE_FRAMES: usize = 60 * 60 * 5;
+
+    // nobody at the controls, so the run ends when the lives run out or the stage does
+    fn run_stage(seed: u64) -> App {
+        let mut app = headless_app(Vec2::new(WINDOW_SIZE_WIDTH, WINDOW_SIZE_HEIGHT), GameSeed(seed), ReplayMode::Off);
+        app.finish();
+        app.cleanup();
+        for _ in 0..STAGE_FRAMES {
+            app.update();
+            if *app.world.resource::<State<GameState>>().get() == GameState::Results {
+                break;
+            }
         }
+        app
+    }
+
+    #[test]
+    fn stage_runs_to_results() {
+        let app = run_stage(1);
+        assert_eq!(*app.world.resource::<State<GameState>>().get(), GameState::Results);
+
+        let player_status = app.world.resource::<PlayerStatus>();
+        let game_timer = app.world.resource::<GameTimer>();
+        assert!(game_timer.seconds > 0);
+        assert!(player_status.lives <= PLAYER_LIVES);
+    }
+
+    #[test]
+    fn same_seed_same_run() {
+        let result = |app: App| {
+            let player_status = app.world.resource::<PlayerStatus>();
+            (player_status.score, player_status.graze, player_status.lives, app.world.resource::<GameTimer>().seconds)
+        };
+        assert_eq!(result(run_stage(7)), result(run_stage(7)));
     }
 }
@@ -0,0 +1,199 @@
+use std::f32::consts::PI;
+
+use bevy::{
+    prelude::*,
+    render::{mesh::{Indices, PrimitiveTopology}, view::NoFrustumCulling},
+    sprite::{MaterialMesh2dBundle, Mesh2dHandle},
+};
+use rand::Rng;
+
+use crate::define::*;
+
+// explosions and sparks, only added when there is a window. purely visual, so they roll
+// thread_rng instead of the game streams and never change what a replay does
+pub struct ParticlePlugin;
+
+impl Plugin for ParticlePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Particles>()
+            .add_systems(Startup, particle_setup_system)
+            .add_systems(OnEnter(GameState::Results), particle_clear_system)
+            .add_systems(Update, (
+                particle_emit_system,
+                particle_update_system,
+                particle_mesh_system,
+            ).chain());
+    }
+}
+
+// sent by gameplay, always registered so senders work without the particle plugin
+#[derive(Event, Clone, Copy)]
+pub struct ParticleEvent {
+    pub position: Vec2,
+    pub emitter: ParticleEmitter,
+}
+
+// one burst, colors and sizes go from the first value to the second over each particle's life
+#[derive(Clone, Copy)]
+pub struct ParticleEmitter {
+    pub count: usize,
+    pub lifetime: f32,
+    // launch angle range in radians, 0 is right
+    pub angle: (f32, f32),
+    // units per second
+    pub speed: (f32, f32),
+    pub color: (Color, Color),
+    pub size: (f32, f32),
+}
+
+impl ParticleEmitter {
+    pub const ENEMY_DEFEATED: Self = Self {
+        count: 24,
+        lifetime: 0.5,
+        angle: (0.0, 2.0 * PI),
+        speed: (40.0, 160.0),
+        color: (Color::rgba(1.0, 0.8, 0.2, 1.0), Color::rgba(1.0, 0.1, 0.0, 0.0)),
+        size: (6.0, 1.0),
+    };
+
+    pub const PLAYER_DEFEATED: Self = Self {
+        count: 48,
+        lifetime: 1.0,
+        angle: (0.0, 2.0 * PI),
+        speed: (30.0, 200.0),
+        color: (Color::rgba(0.4, 0.6, 1.0, 1.0), Color::rgba(1.0, 1.0, 1.0, 0.0)),
+        size: (8.0, 1.0),
+    };
+
+    pub const BULLET_CANCEL: Self = Self {
+        count: 3,
+        lifetime: 0.3,
+        angle: (0.0, 2.0 * PI),
+        speed: (10.0, 40.0),
+        color: (Color::rgba(1.0, 0.2, 0.2, 1.0), Color::rgba(1.0, 0.2, 0.2, 0.0)),
+        size: (4.0, 0.0),
+    };
+
+    // thrown back down the way the shot came
+    pub const HIT_SPARK: Self = Self {
+        count: 5,
+        lifetime: 0.15,
+        angle: (-0.5 * PI - 0.6, -0.5 * PI + 0.6),
+        speed: (80.0, 160.0),
+        color: (Color::rgba(1.0, 1.0, 1.0, 1.0), Color::rgba(1.0, 1.0, 0.3, 0.0)),
+        size: (3.0, 1.0),
+    };
+}
+
+struct Particle {
+    position: Vec2,
+    velocity: Vec2,
+    age: f32,
+    lifetime: f32,
+    color: (Vec4, Vec4),
+    size: (f32, f32),
+}
+
+// kept out of the ECS, the whole set is drawn as one mesh with one material
+#[derive(Resource, Default)]
+struct Particles {
+    particles: Vec<Particle>,
+    mesh: Mesh2dHandle,
+}
+
+fn particle_setup_system(
+    mut commands: Commands,
+    mut particles: ResMut<Particles>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    // vertex colors tint the white material per particle
+    particles.mesh = meshes.add(particle_mesh(&[])).into();
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: particles.mesh.clone(),
+            material: materials.add(ColorMaterial::from(Color::WHITE)),
+            transform: Transform::from_xyz(0.0, 0.0, 5.0),
+            ..default()
+        },
+        // the bounds change every frame
+        NoFrustumCulling,
+    ));
+}
+
+fn particle_clear_system(
+    mut particles: ResMut<Particles>,
+) {
+    particles.particles.clear();
+}
+
+fn particle_emit_system(
+    mut particles: ResMut<Particles>,
+    mut particle_events: EventReader<ParticleEvent>,
+) {
+    let mut rng = rand::thread_rng();
+    for event in particle_events.read() {
+        let emitter = event.emitter;
+        let room = PARTICLE_MAX.saturating_sub(particles.particles.len());
+        for _ in 0..emitter.count.min(room) {
+            let angle = rng.gen_range(emitter.angle.0..=emitter.angle.1);
+            let speed = rng.gen_range(emitter.speed.0..=emitter.speed.1);
+            particles.particles.push(Particle {
+                position: event.position,
+                velocity: Vec2::from_angle(angle) * speed,
+                age: 0.0,
+                lifetime: emitter.lifetime,
+                color: (Vec4::from(emitter.color.0.as_linear_rgba_f32()), Vec4::from(emitter.color.1.as_linear_rgba_f32())),
+                size: emitter.size,
+            });
+        }
+    }
+}
+
+fn particle_update_system(
+    mut particles: ResMut<Particles>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_seconds();
+    particles.particles.retain_mut(|particle| {
+        particle.age += delta;
+        particle.position += particle.velocity * delta;
+        particle.age < particle.lifetime
+    });
+}
+
+fn particle_mesh_system(
+    particles: Res<Particles>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if let Some(mesh) = meshes.get_mut(&particles.mesh.0) {
+        *mesh = particle_mesh(&particles.particles);
+    }
+}
+
+// a quad per particle, the attributes are there even when it is empty
+fn particle_mesh(particles: &[Particle]) -> Mesh {
+    let count = particles.len();
+    let mut positions = Vec::with_capacity(count * 4);
+    let mut colors = Vec::with_capacity(count * 4);
+    let mut indices = Vec::with_capacity(count * 6);
+    for particle in particles.iter() {
+        let t = particle.age / particle.lifetime;
+        let half = (particle.size.0 + (particle.size.1 - particle.size.0) * t) / 2.0;
+        let color = particle.color.0.lerp(particle.color.1, t).to_array();
+
+        let base = positions.len() as u32;
+        for corner in [Vec2::new(-half, -half), Vec2::new(half, -half), Vec2::new(half, half), Vec2::new(-half, half)] {
+            positions.push((particle.position + corner).extend(0.0).to_array());
+            colors.push(color);
+        }
+        indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
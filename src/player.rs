@@ -1,30 +1,39 @@
-use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+use bevy::prelude::*;
 
-use crate::{WindowSizeLimit, PlayerStatus, SpeedControl, SHOOT_RADIUS, Velocity, SHOOT_VELOCITY, AutoDespawn, PLAYER_RADIUS, PLAYER_VELOCITY, FromPlayerShoot, Player, define::{PlayerStartPosition, ShootType}};
+use crate::{input::{GameInput, GameButton}, common::BulletPool, sound::SoundEvent, define::Damage, WindowSizeLimit, PlayerStatus, SpeedControl, Velocity, SHOOT_VELOCITY, SHOOT_SPREAD, SHOOT_FOCUS_SPREAD, PLAYER_RADIUS, PLAYER_VELOCITY, PLAYER_FOCUS_VELOCITY, PLAYER_START_VELOCITY, PLAYER_HITBOX_RADIUS, Hitbox, FromPlayerShoot, Player, define::{Cheats, POWER_LEVELS, PlayerStartPosition, ShootType, GameState, GameSet, OnGameScreen, EXTEND_SCORES}};
 
 pub struct PlayerPlugin;
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (
+        app.add_systems(FixedUpdate, (
             player_status_system,
+            player_extend_system,
+            player_chain_system,
             player_move_system,
+            player_shoot_type.run_if(resource_exists::<Cheats>()),
+            player_power_system,
             player_shoot_system,
             player_start_position,
-            player_shoot_type,
-        ));
+        ).chain().in_set(GameSet::Player).run_if(in_state(GameState::InGame)));
     }
 }
 
 fn player_status_system(
     mut commands: Commands,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
     window_size_limit: Res<WindowSizeLimit>,
     mut player_status: ResMut<PlayerStatus>,
-    time: Res<Time<Virtual>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    time: Res<Time>,
 ) {
-    if player_status.is_spawn == true && player_status.spawn_timer.tick(time.delta()).just_finished() {
+    if player_status.is_spawn && player_status.spawn_timer.tick(time.delta()).just_finished() {
+        // no lives left
+        if player_status.lives == 0 {
+            player_status.is_spawn = false;
+            next_state.set(GameState::GameOver);
+            return;
+        }
+
         // set player init position
         let player = Player {
             x: 0.0,
@@ -32,51 +41,56 @@ fn player_status_system(
             z: 10.0,
             is_enable: false,
             shoot_type: ShootType::Normal,
+            is_focus: false,
         };
 
         // player
         commands.spawn(
-            (MaterialMesh2dBundle {
-                mesh: meshes.add(shape::Circle::new(PLAYER_RADIUS).into()).into(),
-                material: materials.add(ColorMaterial::from(Color::BLUE)),
-                transform: Transform {
-                    translation: Vec3::new(player.x, player.y, player.z),
-                    ..default()
-                },
-                ..default()
-            },
+            (SpatialBundle::from_transform(Transform::from_xyz(player.x, player.y, player.z)),
             player,
+            Hitbox::circle(PLAYER_HITBOX_RADIUS),
             PlayerStartPosition,
+            OnGameScreen,
         ));
 
         player_status.is_spawn = false;
     }
 }
 
+fn player_extend_system(
+    mut player_status: ResMut<PlayerStatus>,
+) {
+    while let Some(extend_score) = EXTEND_SCORES.get(player_status.extend_index) {
+        if player_status.score < *extend_score {
+            break;
+        }
+        player_status.lives += 1;
+        player_status.extend_index += 1;
+    }
+}
+
+fn player_chain_system(
+    mut player_status: ResMut<PlayerStatus>,
+    time: Res<Time>,
+) {
+    if player_status.chain > 0 && player_status.chain_timer.tick(time.delta()).just_finished() {
+        player_status.chain_decay();
+    }
+}
+
 fn player_move_system(
-    input: Res<Input<KeyCode>>,
+    input: Res<GameInput>,
     mut query: Query<(&mut Transform, &mut Player)>,
     speed_control: Res<SpeedControl>,
+    time: Res<Time>,
 ) {
     if let Ok((mut player_transform, mut player_position)) = query.get_single_mut() {
         if player_position.is_enable {
-            let speed = PLAYER_VELOCITY * speed_control.value;
-
-            if input.pressed(KeyCode::Up) {
-                player_position.y += speed;
-            }
-
-            if input.pressed(KeyCode::Down) {
-                player_position.y -= speed;
-            }
-
-            if input.pressed(KeyCode::Right) {
-                player_position.x += speed;
-            }
-
-            if input.pressed(KeyCode::Left) {
-                player_position.x -= speed;
-            }
+            player_position.is_focus = input.pressed(GameButton::Focus);
+            let velocity = if player_position.is_focus { PLAYER_FOCUS_VELOCITY } else { PLAYER_VELOCITY };
+            let movement = input.movement() * velocity * speed_control.value * time.delta_seconds();
+            player_position.x += movement.x;
+            player_position.y += movement.y;
 
             player_transform.translation = player_position.get_position();
         }
@@ -85,90 +99,68 @@ fn player_move_system(
 
 fn player_shoot_system(
     mut commands: Commands,
+    mut bullet_pool: ResMut<BulletPool>,
     query: Query<&Player>,
-    input: Res<Input<KeyCode>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    mut meshes: ResMut<Assets<Mesh>>,
+    input: Res<GameInput>,
     speed_control: Res<SpeedControl>,
+    mut sound_events: EventWriter<SoundEvent>,
 ) {
-    if input.just_pressed(KeyCode::Space) {
+    if input.tick_just_pressed(GameButton::Shoot) {
         if let Ok(player_position) = query.get_single() {
+            sound_events.send(SoundEvent::PlayerShoot);
+
+            let velocity = SHOOT_VELOCITY * speed_control.value;
+            // focus narrows the spread to keep every shot on one target
+            let spread = velocity * if player_position.is_focus { SHOOT_FOCUS_SPREAD } else { SHOOT_SPREAD };
+            let damage = player_position.shoot_type.damage();
+            let center = player_position.set_z_position(0.0);
+            let left = Vec3::new(player_position.x - PLAYER_RADIUS, player_position.y, 0.0);
+            let right = Vec3::new(player_position.x + PLAYER_RADIUS, player_position.y, 0.0);
+
             // player shoot
             match player_position.shoot_type {
                 ShootType::Normal => {
-                    commands.spawn(
-                        (MaterialMesh2dBundle {
-                            mesh: meshes.add(shape::Circle::new(SHOOT_RADIUS).into()).into(),
-                            material: materials.add(ColorMaterial::from(Color::RED)),
-                            transform: Transform::from_translation(player_position.set_z_position(0.0)),
-                            ..default()
-                        },
-                        Velocity { x: 0.0, y: SHOOT_VELOCITY * speed_control.value },
-                        AutoDespawn,
+                    bullet_pool.spawn(&mut commands, center, (
+                        Velocity { x: 0.0, y: velocity },
+                        Damage { value: damage },
+                        Hitbox::player_shoot(),
                         FromPlayerShoot,
                     ));
                 }
                 ShootType::Double => {
-                    let velocity = SHOOT_VELOCITY * speed_control.value;
-                    commands.spawn(
-                        (MaterialMesh2dBundle {
-                            mesh: meshes.add(shape::Circle::new(SHOOT_RADIUS).into()).into(),
-                            material: materials.add(ColorMaterial::from(Color::RED)),
-                            transform: Transform::from_xyz(player_position.x - PLAYER_RADIUS, player_position.y, 0.0),
-                            ..default()
-                        },
-                        Velocity { x: -velocity / 2.0, y: velocity },
-                        AutoDespawn,
+                    bullet_pool.spawn(&mut commands, left, (
+                        Velocity { x: -spread, y: velocity },
+                        Damage { value: damage },
+                        Hitbox::player_shoot(),
                         FromPlayerShoot,
                     ));
 
-                    commands.spawn(
-                        (MaterialMesh2dBundle {
-                            mesh: meshes.add(shape::Circle::new(SHOOT_RADIUS).into()).into(),
-                            material: materials.add(ColorMaterial::from(Color::RED)),
-                            transform: Transform::from_xyz(player_position.x + PLAYER_RADIUS, player_position.y, 0.0),
-                            ..default()
-                        },
-                        Velocity { x: velocity / 2.0, y: velocity },
-                        AutoDespawn,
+                    bullet_pool.spawn(&mut commands, right, (
+                        Velocity { x: spread, y: velocity },
+                        Damage { value: damage },
+                        Hitbox::player_shoot(),
                         FromPlayerShoot,
                     ));
                 }
                 ShootType::Triple => {
-                    commands.spawn(
-                        (MaterialMesh2dBundle {
-                            mesh: meshes.add(shape::Circle::new(SHOOT_RADIUS).into()).into(),
-                            material: materials.add(ColorMaterial::from(Color::RED)),
-                            transform: Transform::from_translation(player_position.set_z_position(0.0)),
-                            ..default()
-                        },
-                        Velocity { x: 0.0, y: SHOOT_VELOCITY * speed_control.value },
-                        AutoDespawn,
+                    bullet_pool.spawn(&mut commands, center, (
+                        Velocity { x: 0.0, y: velocity },
+                        Damage { value: damage },
+                        Hitbox::player_shoot(),
                         FromPlayerShoot,
                     ));
 
-                    let velocity = SHOOT_VELOCITY * speed_control.value;
-                    commands.spawn(
-                        (MaterialMesh2dBundle {
-                            mesh: meshes.add(shape::Circle::new(SHOOT_RADIUS).into()).into(),
-                            material: materials.add(ColorMaterial::from(Color::RED)),
-                            transform: Transform::from_xyz(player_position.x - PLAYER_RADIUS, player_position.y, 0.0),
-                            ..default()
-                        },
-                        Velocity { x: -velocity / 2.0, y: velocity },
-                        AutoDespawn,
+                    bullet_pool.spawn(&mut commands, left, (
+                        Velocity { x: -spread, y: velocity },
+                        Damage { value: damage },
+                        Hitbox::player_shoot(),
                         FromPlayerShoot,
                     ));
 
-                    commands.spawn(
-                        (MaterialMesh2dBundle {
-                            mesh: meshes.add(shape::Circle::new(SHOOT_RADIUS).into()).into(),
-                            material: materials.add(ColorMaterial::from(Color::RED)),
-                            transform: Transform::from_xyz(player_position.x + PLAYER_RADIUS, player_position.y, 0.0),
-                            ..default()
-                        },
-                        Velocity { x: velocity / 2.0, y: velocity },
-                        AutoDespawn,
+                    bullet_pool.spawn(&mut commands, right, (
+                        Velocity { x: spread, y: velocity },
+                        Damage { value: damage },
+                        Hitbox::player_shoot(),
                         FromPlayerShoot,
                     ));
                 }
@@ -181,6 +173,7 @@ fn player_start_position(
     mut commands: Commands,
     window_size_limit: Res<WindowSizeLimit>,
     mut query: Query<(&mut Transform, &mut Player, Entity), With<PlayerStartPosition>>,
+    time: Res<Time>,
 ) {
     let start_position = window_size_limit.bottom + (PLAYER_RADIUS * 12.0);
 
@@ -189,27 +182,65 @@ fn player_start_position(
             commands.entity(entity).remove::<PlayerStartPosition>();
             player.is_enable = true;
         } else {
-            player.y += 5.0;
+            player.y += PLAYER_START_VELOCITY * time.delta_seconds();
         }
     }
 }
 
 fn player_shoot_type(
-    mut query: Query<&mut Player>,
-    input: Res<Input<KeyCode>>,
+    input: Res<GameInput>,
+    mut player_status: ResMut<PlayerStatus>,
 ) {
+    if input.tick_just_pressed(GameButton::ShootNormal) {
+        player_status.power = POWER_LEVELS[0];
+    }
+
+    if input.tick_just_pressed(GameButton::ShootDouble) {
+        player_status.power = POWER_LEVELS[1];
+    }
+
+    if input.tick_just_pressed(GameButton::ShootTriple) {
+        player_status.power = POWER_LEVELS[2];
+    }
+}
 
+fn player_power_system(
+    mut query: Query<&mut Player>,
+    player_status: Res<PlayerStatus>,
+) {
     if let Ok(mut player) = query.get_single_mut() {
-        if input.just_pressed(KeyCode::N) {
-            player.shoot_type = ShootType::Normal;
-        }
+        player.shoot_type = player_status.shoot_type();
+    }
+}
 
-        if input.just_pressed(KeyCode::D) {
-            player.shoot_type = ShootType::Double;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{headless_app, define::{WINDOW_SIZE_WIDTH, WINDOW_SIZE_HEIGHT, POWER_LEVELS}, replay::ReplayMode, rng::GameSeed};
+
+    fn press_triple(app: &mut App) {
+        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::T);
+        for _ in 0..5 {
+            app.update();
         }
+        app.world.resource_mut::<Input<KeyCode>>().release(KeyCode::T);
+        app.update();
+    }
 
-        if input.just_pressed(KeyCode::T) {
-            player.shoot_type = ShootType::Triple;
+    #[test]
+    fn power_keys_need_cheats() {
+        let mut app = headless_app(Vec2::new(WINDOW_SIZE_WIDTH, WINDOW_SIZE_HEIGHT), GameSeed(1), ReplayMode::Off);
+        app.finish();
+        app.cleanup();
+        while *app.world.resource::<State<GameState>>().get() != GameState::InGame {
+            app.update();
         }
+
+        press_triple(&mut app);
+        assert_eq!(app.world.resource::<PlayerStatus>().power, 0);
+
+        app.insert_resource(Cheats);
+        press_triple(&mut app);
+        assert_eq!(app.world.resource::<PlayerStatus>().power, POWER_LEVELS[2]);
     }
-}
\ No newline at end of file
+}
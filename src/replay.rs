@@ -0,0 +1,261 @@
+use std::{fs, path::{Path, PathBuf}, time::Duration};
+
+use bevy::{prelude::*, app::AppExit, time::{TimeSystem, TimeUpdateStrategy}};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{arg_value, define::GameState, input::GameInput, rng::GameSeed, settings::write_atomic};
+
+pub const REPLAY_VERSION: u32 = 4;
+
+// one rendered frame after loading, the real time delta is kept so playback steps time exactly the same way
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct ReplayFrame {
+    pub delta_nanos: u64,
+    pub buttons: u16,
+    pub stick: [i8; 2],
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    pub skip_title: bool,
+    pub cheats: bool,
+    pub frames: Vec<ReplayFrame>,
+}
+
+#[derive(Deserialize)]
+struct ReplayHeader {
+    version: u32,
+}
+
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("could not access replay file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse replay file: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("could not write replay file: {0}")]
+    Write(#[from] ron::Error),
+    #[error("unsupported replay version {0}, expected {REPLAY_VERSION}")]
+    Version(u32),
+}
+
+impl Replay {
+    pub fn new(seed: u64, skip_title: bool, cheats: bool) -> Self {
+        Self { version: REPLAY_VERSION, seed, skip_title, cheats, frames: Vec::new() }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ReplayError> {
+        let text = fs::read_to_string(path)?;
+
+        // check the version before the fields it decides
+        let header: ReplayHeader = ron::from_str(&text)?;
+        if header.version != REPLAY_VERSION {
+            return Err(ReplayError::Version(header.version));
+        }
+        Ok(ron::from_str(&text)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ReplayError> {
+        write_atomic(path, &ron::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub enum ReplayMode {
+    Off,
+    Record(PathBuf),
+    Playback(Replay),
+}
+
+impl ReplayMode {
+    // --record <path> or --replay <path>
+    pub fn from_args() -> Result<Self, ReplayError> {
+        if let Some(path) = arg_value("--replay") {
+            return Ok(ReplayMode::Playback(Replay::load(Path::new(&path))?));
+        }
+        if let Some(path) = arg_value("--record") {
+            return Ok(ReplayMode::Record(PathBuf::from(path)));
+        }
+        Ok(ReplayMode::Off)
+    }
+
+    // a replay starts the same way it was recorded
+    pub fn skip_title(&self, default: bool) -> bool {
+        match self {
+            ReplayMode::Playback(replay) => replay.skip_title,
+            _ => default,
+        }
+    }
+
+    pub fn cheats(&self, default: bool) -> bool {
+        match self {
+            ReplayMode::Playback(replay) => replay.cheats,
+            _ => default,
+        }
+    }
+}
+
+pub struct ReplayPlugin {
+    pub mode: ReplayMode,
+    pub skip_title: bool,
+    pub cheats: bool,
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        match &self.mode {
+            ReplayMode::Off => {}
+            ReplayMode::Record(path) => {
+                let seed = app.world.resource::<GameSeed>().0;
+                app
+                    .insert_resource(ReplayRecorder {
+                        path: path.clone(),
+                        replay: Replay::new(seed, self.skip_title, self.cheats),
+                        is_recording: false,
+                    })
+                    .add_systems(First, replay_record_start_system.before(TimeSystem))
+                    .add_systems(Last, (
+                        replay_record_system,
+                        replay_save_system,
+                    ).chain());
+            }
+            ReplayMode::Playback(replay) => {
+                app
+                    .insert_resource(ReplayPlayback {
+                        replay: replay.clone(),
+                        cursor: 0,
+                    })
+                    .add_systems(First, replay_playback_system
+                        .before(TimeSystem)
+                        .run_if(not(in_state(GameState::Loading))));
+            }
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct ReplayRecorder {
+    path: PathBuf,
+    replay: Replay,
+    // decided at the start of the frame, the same point where playback decides
+    is_recording: bool,
+}
+
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    replay: Replay,
+    cursor: usize,
+}
+
+fn replay_record_start_system(
+    mut recorder: ResMut<ReplayRecorder>,
+    state: Res<State<GameState>>,
+) {
+    recorder.is_recording = *state.get() != GameState::Loading;
+}
+
+fn replay_record_system(
+    mut recorder: ResMut<ReplayRecorder>,
+    game_input: Res<GameInput>,
+    time: Res<Time<Real>>,
+) {
+    if !recorder.is_recording {
+        return;
+    }
+
+    recorder.replay.frames.push(ReplayFrame {
+        delta_nanos: time.delta().as_nanos() as u64,
+        buttons: game_input.buttons(),
+        stick: game_input.stick(),
+    });
+}
+
+fn replay_save_system(
+    recorder: Res<ReplayRecorder>,
+    mut exit_events: EventReader<AppExit>,
+) {
+    if exit_events.read().next().is_some() {
+        match recorder.replay.save(&recorder.path) {
+            Ok(()) => println!("replay saved: {}", recorder.path.display()),
+            Err(error) => eprintln!("{}", error),
+        }
+    }
+}
+
+fn replay_playback_system(
+    mut playback: ResMut<ReplayPlayback>,
+    mut game_input: ResMut<GameInput>,
+    mut time_update_strategy: ResMut<TimeUpdateStrategy>,
+) {
+    match playback.replay.frames.get(playback.cursor).copied() {
+        Some(frame) => {
+            *time_update_strategy = TimeUpdateStrategy::ManualDuration(Duration::from_nanos(frame.delta_nanos));
+            game_input.set_buttons(frame.buttons);
+            game_input.set_stick(frame.stick);
+            playback.cursor += 1;
+            if playback.cursor == playback.replay.frames.len() {
+                println!("replay finished");
+            }
+        }
+        None => {
+            game_input.set_buttons(0);
+            game_input.set_stick([0, 0]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::AppExit;
+
+    use super::*;
+    use crate::{headless_app, define::{PlayerStatus, GameTimer, WINDOW_SIZE_WIDTH, WINDOW_SIZE_HEIGHT}};
+
+    const RECORD_FRAMES: usize = 60 * 20;
+
+    fn result(app: &App) -> (f32, u32, u32, u64) {
+        let player_status = app.world.resource::<PlayerStatus>();
+        (player_status.score, player_status.graze, player_status.lives, app.world.resource::<GameTimer>().seconds)
+    }
+
+    #[test]
+    fn playback_repeats_the_recorded_run() {
+        let path = std::env::temp_dir().join(format!("shoot-game-replay-{}.ron", std::process::id()));
+
+        // shoot the whole time and weave left and right, so the run depends on the input
+        let mut app = headless_app(Vec2::new(WINDOW_SIZE_WIDTH, WINDOW_SIZE_HEIGHT), GameSeed(3), ReplayMode::Record(path.clone()));
+        app.finish();
+        app.cleanup();
+        app.world.resource_mut::<Input<KeyCode>>().press(KeyCode::Space);
+        for frame in 0..RECORD_FRAMES {
+            let mut keyboard = app.world.resource_mut::<Input<KeyCode>>();
+            match frame % 120 {
+                0 => keyboard.press(KeyCode::Left),
+                40 => keyboard.release(KeyCode::Left),
+                60 => keyboard.press(KeyCode::Right),
+                100 => keyboard.release(KeyCode::Right),
+                _ => {}
+            }
+            app.update();
+        }
+        app.world.send_event(AppExit);
+        app.update();
+        let recorded = result(&app);
+        assert!(recorded.0 > 0.0);
+
+        let replay = Replay::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let frames = replay.frames.len();
+        let mut app = headless_app(Vec2::new(WINDOW_SIZE_WIDTH, WINDOW_SIZE_HEIGHT), GameSeed(replay.seed), ReplayMode::Playback(replay));
+        app.finish();
+        app.cleanup();
+        while app.world.resource::<ReplayPlayback>().cursor < frames {
+            app.update();
+        }
+        assert_eq!(result(&app), recorded);
+    }
+}
@@ -0,0 +1,101 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::arg_value;
+
+#[derive(Clone, Copy, Debug)]
+pub enum RngStream {
+    EnemyFire,
+    Drop,
+    Wave,
+}
+
+// seed for every run, from --seed or picked at launch
+#[derive(Resource, Clone, Copy)]
+pub struct GameSeed(pub u64);
+
+impl GameSeed {
+    pub fn from_args() -> Self {
+        match arg_value("--seed").and_then(|value| value.parse().ok()) {
+            Some(seed) => Self(seed),
+            None => Self(rand::thread_rng().gen()),
+        }
+    }
+}
+
+// each stream has its own generator so drawing from one never shifts another
+#[derive(Resource)]
+pub struct GameRng {
+    enemy_fire: StdRng,
+    drop: StdRng,
+    wave: StdRng,
+}
+
+// splitmix64 over the run seed and stream id, so nearby seeds never share a stream
+fn stream_seed(seed: u64, stream: RngStream) -> u64 {
+    let mut z = seed ^ (stream as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            enemy_fire: StdRng::seed_from_u64(stream_seed(seed, RngStream::EnemyFire)),
+            drop: StdRng::seed_from_u64(stream_seed(seed, RngStream::Drop)),
+            wave: StdRng::seed_from_u64(stream_seed(seed, RngStream::Wave)),
+        }
+    }
+
+    pub fn stream(&mut self, stream: RngStream) -> &mut StdRng {
+        match stream {
+            RngStream::EnemyFire => &mut self.enemy_fire,
+            RngStream::Drop => &mut self.drop,
+            RngStream::Wave => &mut self.wave,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enemy_fire(game_rng: &mut GameRng) -> Vec<f32> {
+        (0..16).map(|_| game_rng.stream(RngStream::EnemyFire).gen_range(1.0..=3.0)).collect()
+    }
+
+    #[test]
+    fn same_seed_same_enemy_fire() {
+        assert_eq!(enemy_fire(&mut GameRng::new(42)), enemy_fire(&mut GameRng::new(42)));
+        assert_ne!(enemy_fire(&mut GameRng::new(42)), enemy_fire(&mut GameRng::new(43)));
+    }
+
+    #[test]
+    fn streams_do_not_shift_each_other() {
+        let mut quiet = GameRng::new(42);
+        let mut busy = GameRng::new(42);
+        for _ in 0..5 {
+            let _: f64 = busy.stream(RngStream::Drop).gen();
+            let _: f32 = busy.stream(RngStream::Wave).gen();
+        }
+        assert_eq!(enemy_fire(&mut quiet), enemy_fire(&mut busy));
+    }
+
+    #[test]
+    fn adjacent_seeds_do_not_share_streams() {
+        const STREAMS: [RngStream; 3] = [RngStream::EnemyFire, RngStream::Drop, RngStream::Wave];
+        let draws = |seed: u64| -> Vec<Vec<u64>> {
+            let mut game_rng = GameRng::new(seed);
+            STREAMS
+                .iter()
+                .map(|&stream| (0..8).map(|_| game_rng.stream(stream).gen()).collect())
+                .collect()
+        };
+        let (this, next) = (draws(42), draws(43));
+        for a in this.iter().chain(next.iter()) {
+            let same = this.iter().chain(next.iter()).filter(|b| *b == a).count();
+            assert_eq!(same, 1);
+        }
+    }
+}
@@ -0,0 +1,217 @@
+use bevy::prelude::*;
+
+use crate::{define::*, input::{GameInput, GameButton, KeyBindings}, highscore::{HighScores, run_end_state}};
+
+pub struct ScreenPlugin;
+
+impl Plugin for ScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            // title
+            .add_systems(OnEnter(GameState::Title), title_setup_system)
+            .add_systems(Update, title_input_system.run_if(in_state(GameState::Title)))
+            .add_systems(OnExit(GameState::Title), despawn_screen::<OnTitleScreen>)
+            // pause
+            .add_systems(OnEnter(GameState::Paused), pause_setup_system)
+            .add_systems(Update, pause_input_system.run_if(in_state(GameState::Paused)))
+            .add_systems(OnExit(GameState::Paused), (
+                despawn_screen::<OnPauseScreen>,
+                pause_cleanup_system,
+            ))
+            // game over
+            .add_systems(OnEnter(GameState::GameOver), game_over_setup_system)
+            .add_systems(Update, game_over_input_system.run_if(in_state(GameState::GameOver)))
+            .add_systems(OnExit(GameState::GameOver), despawn_screen::<OnGameOverScreen>)
+            // results
+            .add_systems(OnEnter(GameState::Results), results_setup_system)
+            .add_systems(Update, results_input_system.run_if(in_state(GameState::Results)))
+            .add_systems(OnExit(GameState::Results), despawn_screen::<OnResultsScreen>);
+    }
+}
+
+pub fn despawn_screen<T: Component>(
+    mut commands: Commands,
+    query: Query<Entity, With<T>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn screen_node() -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            display: Display::Flex,
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            row_gap: Val::Px(20.0),
+            width: Val::Percent(100.),
+            height: Val::Percent(100.),
+            position_type: PositionType::Absolute,
+            ..default()
+        },
+        ..default()
+    }
+}
+
+pub fn screen_text(value: impl Into<String>, font_size: f32) -> TextBundle {
+    TextBundle::from_section(
+        value,
+        TextStyle {
+            font_size,
+            ..default()
+        },
+    )
+}
+
+fn title_setup_system(
+    mut commands: Commands,
+    high_scores: Option<Res<HighScores>>,
+    key_bindings: Res<KeyBindings>,
+) {
+    commands.spawn((screen_node(), OnTitleScreen)).with_children(|p| {
+        p.spawn(screen_text("shooting game!!!", 40.0));
+        p.spawn(screen_text(format!("press {} to start", key_bindings.name(GameButton::Shoot)), 20.0));
+        p.spawn(screen_text(format!("{}: key config", key_bindings.name(GameButton::Cancel)), 20.0));
+
+        if let Some(high_scores) = high_scores {
+            p.spawn(screen_text("high scores", 20.0));
+            for (rank, entry) in high_scores.entries().iter().enumerate() {
+                p.spawn(screen_text(format!("{:>2}. {} {:04}", rank + 1, entry.name, entry.score), 16.0));
+            }
+        }
+    });
+}
+
+fn title_input_system(
+    mut commands: Commands,
+    input: Res<GameInput>,
+    skip_title: Option<Res<SkipTitle>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if skip_title.is_some() {
+        commands.remove_resource::<SkipTitle>();
+        next_state.set(GameState::InGame);
+    } else if input.just_pressed(GameButton::Shoot) {
+        next_state.set(GameState::InGame);
+    } else if input.just_pressed(GameButton::Cancel) {
+        next_state.set(GameState::KeyConfig);
+    }
+}
+
+fn pause_setup_system(
+    mut commands: Commands,
+    mut time: ResMut<Time<Virtual>>,
+    key_bindings: Res<KeyBindings>,
+) {
+    time.pause();
+
+    commands.spawn((screen_node(), OnPauseScreen)).with_children(|p| {
+        p.spawn(screen_text("pause", 40.0));
+        p.spawn(screen_text(format!(
+            "{}: resume / {}: give up",
+            key_bindings.name(GameButton::Pause),
+            key_bindings.name(GameButton::Cancel),
+        ), 20.0));
+    });
+}
+
+fn pause_input_system(
+    input: Res<GameInput>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    // resume is handled by toggle_pause
+    if input.just_pressed(GameButton::Cancel) {
+        next_state.set(GameState::GameOver);
+    }
+}
+
+fn pause_cleanup_system(
+    mut time: ResMut<Time<Virtual>>,
+) {
+    time.unpause();
+}
+
+fn game_over_setup_system(
+    mut commands: Commands,
+    player_status: Res<PlayerStatus>,
+    key_bindings: Res<KeyBindings>,
+) {
+    // continue is only offered when the run ended by losing every life
+    let can_continue = CONTINUE_ENABLE && player_status.lives == 0;
+    let seconds = if can_continue { CONTINUE_SECONDS } else { GAME_OVER_SCREEN_SECONDS };
+
+    commands.spawn((
+        screen_node(),
+        OnGameOverScreen {
+            timer: Timer::from_seconds(seconds, TimerMode::Once),
+            can_continue,
+        },
+    )).with_children(|p| {
+        p.spawn(screen_text("game over", 40.0));
+        if can_continue {
+            p.spawn((screen_text("", 20.0), ContinueCountdown));
+            p.spawn(screen_text(format!(
+                "{}: continue / {}: give up",
+                key_bindings.name(GameButton::Shoot),
+                key_bindings.name(GameButton::Cancel),
+            ), 20.0));
+        }
+    });
+}
+
+fn game_over_input_system(
+    input: Res<GameInput>,
+    mut query: Query<&mut OnGameOverScreen>,
+    mut countdown_query: Query<&mut Text, With<ContinueCountdown>>,
+    mut player_status: ResMut<PlayerStatus>,
+    high_scores: Option<Res<HighScores>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    time: Res<Time<Virtual>>,
+) {
+    if let Ok(mut game_over) = query.get_single_mut() {
+        let finished = game_over.timer.tick(time.delta()).finished();
+        let run_end = run_end_state(high_scores.as_deref(), &player_status);
+
+        if game_over.can_continue {
+            for mut text in countdown_query.iter_mut() {
+                text.sections[0].value = format!("continue? {}", game_over.timer.remaining_secs().ceil() as u32);
+            }
+
+            if input.just_pressed(GameButton::Shoot) {
+                player_status.continue_game();
+                next_state.set(GameState::InGame);
+            } else if finished || input.just_pressed(GameButton::Cancel) {
+                next_state.set(run_end);
+            }
+        } else if finished || input.just_pressed(GameButton::Shoot) {
+            next_state.set(run_end);
+        }
+    }
+}
+
+fn results_setup_system(
+    mut commands: Commands,
+    player_status: Res<PlayerStatus>,
+    game_timer: Res<GameTimer>,
+    key_bindings: Res<KeyBindings>,
+) {
+    commands.spawn((screen_node(), OnResultsScreen)).with_children(|p| {
+        p.spawn(screen_text("results", 40.0));
+        p.spawn(screen_text(format!("score: {:04}", player_status.score as u32), 20.0));
+        p.spawn(screen_text(format!("graze: {}", player_status.graze), 20.0));
+        p.spawn(screen_text(format!("time: {}s", game_timer.seconds), 20.0));
+        p.spawn(screen_text(format!("continue: {}", player_status.continues), 20.0));
+        p.spawn(screen_text(format!("press {} to title", key_bindings.name(GameButton::Shoot)), 20.0));
+    });
+}
+
+fn results_input_system(
+    input: Res<GameInput>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if input.just_pressed(GameButton::Shoot) {
+        next_state.set(GameState::Title);
+    }
+}
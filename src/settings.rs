@@ -0,0 +1,335 @@
+use std::{env, fs, io::Write, path::{Path, PathBuf}};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{define::*, input::{GameInput, GameButton, KeyBindings}, screen::{despawn_screen, screen_node, screen_text}};
+
+pub const SETTINGS_VERSION: u32 = 1;
+pub const SETTINGS_FILE: &str = "settings.ron";
+
+// key bindings from the settings file and the screen to change them
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<AudioVolume>()
+            .add_systems(Startup, settings_load_system)
+            .add_systems(OnEnter(GameState::KeyConfig), key_config_setup_system)
+            .add_systems(Update, key_config_input_system.run_if(in_state(GameState::KeyConfig)))
+            .add_systems(OnExit(GameState::KeyConfig), (
+                despawn_screen::<OnKeyConfigScreen>,
+                settings_save_system,
+            ));
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Settings {
+    pub version: u32,
+    pub bindings: Vec<(GameButton, KeyCode)>,
+    // files written before volumes existed get the defaults
+    #[serde(default = "default_music_volume")]
+    pub music_volume: f32,
+    #[serde(default = "default_sfx_volume")]
+    pub sfx_volume: f32,
+}
+
+fn default_music_volume() -> f32 {
+    AudioVolume::default().music
+}
+
+fn default_sfx_volume() -> f32 {
+    AudioVolume::default().sfx
+}
+
+// 0.0 to 1.0, music and sound effects apart
+#[derive(Resource, Clone, Copy)]
+pub struct AudioVolume {
+    pub music: f32,
+    pub sfx: f32,
+}
+
+impl Default for AudioVolume {
+    fn default() -> Self {
+        Self { music: 0.5, sfx: 0.5 }
+    }
+}
+
+#[derive(Deserialize)]
+struct SettingsHeader {
+    version: u32,
+}
+
+#[derive(Debug, Error)]
+pub enum SettingsError {
+    #[error("could not access settings file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse settings file: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error("could not write settings file: {0}")]
+    Write(#[from] ron::Error),
+    #[error("unsupported settings version {0}, expected {SETTINGS_VERSION}")]
+    Version(u32),
+    #[error("{0:?} and {1:?} are both bound to {2:?}")]
+    Conflict(GameButton, GameButton, KeyCode),
+}
+
+impl Settings {
+    pub fn new(key_bindings: &KeyBindings, audio_volume: &AudioVolume) -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            bindings: GameButton::ALL.iter().map(|button| (*button, key_bindings.key(*button))).collect(),
+            music_volume: audio_volume.music,
+            sfx_volume: audio_volume.sfx,
+        }
+    }
+
+    // a missing file keeps the defaults
+    pub fn load(path: &Path) -> Result<Option<Self>, SettingsError> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+
+        // check the version before the fields it decides
+        let header: SettingsHeader = ron::from_str(&text)?;
+        if header.version != SETTINGS_VERSION {
+            return Err(SettingsError::Version(header.version));
+        }
+        Ok(Some(ron::from_str(&text)?))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), SettingsError> {
+        write_atomic(path, &ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?)?;
+        Ok(())
+    }
+
+    // buttons left out of the file keep their default key, two buttons on one key is an error
+    pub fn key_bindings(&self) -> Result<KeyBindings, SettingsError> {
+        let mut key_bindings = KeyBindings::default();
+        for (button, key) in self.bindings.iter() {
+            key_bindings.bind(*button, *key);
+        }
+        for button in GameButton::ALL {
+            if let Some(other) = key_bindings.conflict(button, key_bindings.key(button)) {
+                return Err(SettingsError::Conflict(button, other, key_bindings.key(button)));
+            }
+        }
+        Ok(key_bindings)
+    }
+
+    pub fn audio_volume(&self) -> AudioVolume {
+        AudioVolume {
+            music: self.music_volume.clamp(0.0, 1.0),
+            sfx: self.sfx_volume.clamp(0.0, 1.0),
+        }
+    }
+}
+
+// file under the per-user data directory, the working directory when none can be found
+pub fn data_path(file: &str) -> PathBuf {
+    let data_dir = if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env::var_os("XDG_DATA_HOME").map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+    };
+
+    match data_dir {
+        Some(data_dir) => data_dir.join("shoot-game").join(file),
+        None => PathBuf::from(file),
+    }
+}
+
+// written next to the real file and renamed over it, a crash never leaves half a file
+pub fn write_atomic(path: &Path, text: &str) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let mut file = fs::File::create(&temp_path)?;
+    file.write_all(text.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)
+}
+
+fn settings_load_system(
+    mut key_bindings: ResMut<KeyBindings>,
+    mut audio_volume: ResMut<AudioVolume>,
+) {
+    let settings = match Settings::load(&data_path(SETTINGS_FILE)) {
+        Ok(Some(settings)) => settings,
+        Ok(None) => return,
+        Err(error) => {
+            eprintln!("{}", error);
+            return;
+        }
+    };
+
+    *audio_volume = settings.audio_volume();
+    match settings.key_bindings() {
+        Ok(loaded) => *key_bindings = loaded,
+        Err(error) => eprintln!("{}", error),
+    }
+}
+
+fn settings_save_system(
+    key_bindings: Res<KeyBindings>,
+    audio_volume: Res<AudioVolume>,
+) {
+    if let Err(error) = Settings::new(&key_bindings, &audio_volume).save(&data_path(SETTINGS_FILE)) {
+        eprintln!("{}", error);
+    }
+}
+
+// row under the cursor, waiting for a key once picked
+#[derive(Component)]
+struct KeyConfig {
+    cursor: usize,
+    is_waiting: bool,
+}
+
+#[derive(Component)]
+struct KeyConfigRow(GameButton);
+
+#[derive(Component)]
+struct KeyConfigMessage;
+
+fn key_config_setup_system(
+    mut commands: Commands,
+    key_bindings: Res<KeyBindings>,
+) {
+    commands.spawn((screen_node(), KeyConfig { cursor: 0, is_waiting: false }, OnKeyConfigScreen)).with_children(|p| {
+        p.spawn(screen_text("key config", 40.0));
+        for button in GameButton::ALL {
+            p.spawn((screen_text("", 16.0), KeyConfigRow(button)));
+        }
+        p.spawn((screen_text("", 16.0), KeyConfigMessage));
+        p.spawn(screen_text(format!(
+            "{}: change / {}: back",
+            key_bindings.name(GameButton::Shoot),
+            key_bindings.name(GameButton::Cancel),
+        ), 20.0));
+    });
+}
+
+// moves through GameInput like every other screen, the raw keyboard is read only to capture the key being bound
+fn key_config_input_system(
+    input: Res<GameInput>,
+    keyboard: Res<Input<KeyCode>>,
+    mut key_bindings: ResMut<KeyBindings>,
+    mut key_config: Query<&mut KeyConfig>,
+    mut rows: Query<(&mut Text, &KeyConfigRow), Without<KeyConfigMessage>>,
+    mut message: Query<&mut Text, With<KeyConfigMessage>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Ok(mut key_config) = key_config.get_single_mut() else {
+        return;
+    };
+    if key_config.is_waiting {
+        let button = GameButton::ALL[key_config.cursor];
+        if input.just_pressed(GameButton::Cancel) {
+            // cancel leaves the binding as it was
+            for mut message in message.iter_mut() {
+                message.sections[0].value.clear();
+            }
+            key_config.is_waiting = false;
+        } else if let Some(key) = keyboard.get_just_pressed().next().copied() {
+            // the press that started waiting was last frame, so this is a new key
+            let text = match key_bindings.conflict(button, key) {
+                Some(other) => format!("{:?} is already used by {:?}", key, other),
+                None => {
+                    key_bindings.bind(button, key);
+                    String::new()
+                }
+            };
+            for mut message in message.iter_mut() {
+                message.sections[0].value = text.clone();
+            }
+            key_config.is_waiting = false;
+        }
+    } else if input.just_pressed(GameButton::Up) {
+        key_config.cursor = (key_config.cursor + GameButton::ALL.len() - 1) % GameButton::ALL.len();
+    } else if input.just_pressed(GameButton::Down) {
+        key_config.cursor = (key_config.cursor + 1) % GameButton::ALL.len();
+    } else if input.just_pressed(GameButton::Shoot) {
+        key_config.is_waiting = true;
+    } else if input.just_pressed(GameButton::Cancel) {
+        next_state.set(GameState::Title);
+    }
+
+    let button = GameButton::ALL[key_config.cursor];
+    for (mut text, row) in rows.iter_mut() {
+        let marker = if row.0 == button { ">" } else { " " };
+        let key = if row.0 == button && key_config.is_waiting { "...".to_string() } else { key_bindings.name(row.0) };
+        text.sections[0].value = format!("{} {:?}: {}", marker, row.0, key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("shoot-game-settings-{}-{}.ron", std::process::id(), name))
+    }
+
+    fn load_text(name: &str, text: &str) -> Result<Option<Settings>, SettingsError> {
+        let path = temp_path(name);
+        fs::write(&path, text).unwrap();
+        let settings = Settings::load(&path);
+        fs::remove_file(&path).unwrap();
+        settings
+    }
+
+    #[test]
+    fn missing_file_keeps_defaults() {
+        assert!(Settings::load(&temp_path("missing")).unwrap().is_none());
+    }
+
+    #[test]
+    fn wrong_version_is_rejected() {
+        let settings = load_text("version", "(version: 99, bindings: [])");
+        assert!(matches!(settings, Err(SettingsError::Version(99))));
+    }
+
+    #[test]
+    fn partial_bindings_keep_the_other_defaults() {
+        let settings = load_text("partial", "(version: 1, bindings: [(Shoot, J), (Bomb, K)])").unwrap().unwrap();
+        let key_bindings = settings.key_bindings().unwrap();
+        assert_eq!(key_bindings.key(GameButton::Shoot), KeyCode::J);
+        assert_eq!(key_bindings.key(GameButton::Bomb), KeyCode::K);
+        assert_eq!(key_bindings.key(GameButton::Up), KeyBindings::default().key(GameButton::Up));
+
+        // volumes written before they existed
+        let audio_volume = settings.audio_volume();
+        assert_eq!((audio_volume.music, audio_volume.sfx), (AudioVolume::default().music, AudioVolume::default().sfx));
+    }
+
+    #[test]
+    fn shared_key_is_a_conflict() {
+        let settings = load_text("conflict", "(version: 1, bindings: [(Shoot, X)])").unwrap().unwrap();
+        // X is the default bomb key
+        assert!(matches!(settings.key_bindings(), Err(SettingsError::Conflict(_, _, KeyCode::X))));
+    }
+
+    #[test]
+    fn settings_survive_a_save() {
+        let mut key_bindings = KeyBindings::default();
+        key_bindings.bind(GameButton::Pause, KeyCode::Return);
+        let path = temp_path("save");
+        Settings::new(&key_bindings, &AudioVolume { music: 0.2, sfx: 0.8 }).save(&path).unwrap();
+
+        let settings = Settings::load(&path).unwrap().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(settings.key_bindings().unwrap().key(GameButton::Pause), KeyCode::Return);
+        assert_eq!(settings.audio_volume().music, 0.2);
+    }
+}
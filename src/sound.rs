@@ -0,0 +1,135 @@
+use bevy::{prelude::*, audio::Volume, utils::HashMap};
+
+use crate::{define::*, enemy::EnemyDefeatedEvent, settings::AudioVolume};
+
+pub const MUSIC_PATH: &str = "sounds/music_stage.wav";
+
+// plays the sound events, only added when there is a window so headless runs stay silent
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, sound_assets_setup_system)
+            .add_systems(Update, sound_system)
+            .add_systems(OnEnter(GameState::Paused), music_pause_system)
+            .add_systems(OnExit(GameState::Paused), music_resume_system);
+    }
+}
+
+// sent by gameplay, always registered so senders work without the sound plugin
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SoundEvent {
+    PlayerShoot,
+    EnemyShoot,
+    EnemyDefeated,
+    PlayerDefeated,
+    ItemPickup,
+    StageStart,
+}
+
+impl SoundEvent {
+    pub const ALL: [SoundEvent; 6] = [
+        SoundEvent::PlayerShoot,
+        SoundEvent::EnemyShoot,
+        SoundEvent::EnemyDefeated,
+        SoundEvent::PlayerDefeated,
+        SoundEvent::ItemPickup,
+        SoundEvent::StageStart,
+    ];
+
+    fn path(self) -> &'static str {
+        match self {
+            SoundEvent::PlayerShoot => "sounds/player_shoot.wav",
+            SoundEvent::EnemyShoot => "sounds/enemy_shoot.wav",
+            SoundEvent::EnemyDefeated => "sounds/enemy_defeated.wav",
+            SoundEvent::PlayerDefeated => "sounds/player_defeated.wav",
+            SoundEvent::ItemPickup => "sounds/item_pickup.wav",
+            SoundEvent::StageStart => "sounds/stage_start.wav",
+        }
+    }
+}
+
+#[derive(Resource)]
+struct SoundAssets {
+    sounds: HashMap<SoundEvent, Handle<AudioSource>>,
+    music: Handle<AudioSource>,
+}
+
+// one playing copy of a sound, gone when it finishes
+#[derive(Component)]
+struct SoundVoice(SoundEvent);
+
+#[derive(Component)]
+struct Music;
+
+fn sound_assets_setup_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    commands.insert_resource(SoundAssets {
+        sounds: SoundEvent::ALL.iter().map(|sound| (*sound, asset_server.load(sound.path()))).collect(),
+        music: asset_server.load(MUSIC_PATH),
+    });
+}
+
+fn sound_system(
+    mut commands: Commands,
+    sound_assets: Res<SoundAssets>,
+    audio_volume: Res<AudioVolume>,
+    mut sound_events: EventReader<SoundEvent>,
+    mut defeated_events: EventReader<EnemyDefeatedEvent>,
+    voices: Query<&SoundVoice>,
+    music: Query<(), With<Music>>,
+) {
+    let mut playing: HashMap<SoundEvent, usize> = HashMap::new();
+    for voice in voices.iter() {
+        *playing.entry(voice.0).or_default() += 1;
+    }
+
+    let defeated = defeated_events.read().map(|_| SoundEvent::EnemyDefeated);
+    for sound in sound_events.read().copied().chain(defeated) {
+        // the stage music starts with the stage and stays until the game screen goes
+        if sound == SoundEvent::StageStart && music.is_empty() {
+            commands.spawn((
+                AudioBundle {
+                    source: sound_assets.music.clone(),
+                    settings: PlaybackSettings::LOOP.with_volume(Volume::new_relative(audio_volume.music)),
+                },
+                Music,
+                OnGameScreen,
+            ));
+        }
+
+        // more copies of one sound only get louder and clip
+        let count = playing.entry(sound).or_default();
+        if *count >= SOUND_MAX_VOICES {
+            continue;
+        }
+        *count += 1;
+
+        commands.spawn((
+            AudioBundle {
+                source: sound_assets.sounds[&sound].clone(),
+                settings: PlaybackSettings::DESPAWN.with_volume(Volume::new_relative(audio_volume.sfx)),
+            },
+            SoundVoice(sound),
+        ));
+    }
+}
+
+fn music_pause_system(
+    music: Query<&AudioSink, With<Music>>,
+) {
+    for sink in music.iter() {
+        sink.pause();
+    }
+}
+
+fn music_resume_system(
+    music: Query<&AudioSink, With<Music>>,
+) {
+    for sink in music.iter() {
+        sink.play();
+    }
+}
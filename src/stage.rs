@@ -0,0 +1,170 @@
+use bevy::{
+    asset::{AssetLoader, AsyncReadExt, LoadContext, LoadState, RecursiveDependencyLoadState, io::Reader},
+    prelude::*,
+    reflect::TypePath,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{define::{GameState, PlayerStatus}, enemy::bulletml::BulletMl, highscore::{HighScores, run_end_state}};
+
+pub const STAGE_PATH: &str = "stages/stage_001.stage.ron";
+
+pub struct StagePlugin;
+
+impl Plugin for StagePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_asset::<Stage>()
+            .init_asset_loader::<StageLoader>()
+            .add_event::<StageClearEvent>()
+            .add_systems(Startup, stage_load_system)
+            .add_systems(OnEnter(GameState::Loading), stage_loading_setup_system)
+            .add_systems(Update, stage_loading_system.run_if(in_state(GameState::Loading)))
+            .add_systems(OnExit(GameState::Loading), stage_loading_cleanup_system)
+            .add_systems(Update, stage_clear_system.run_if(in_state(GameState::InGame)));
+    }
+}
+
+// sent when the stage boss goes down or leaves
+#[derive(Event)]
+pub struct StageClearEvent;
+
+#[derive(Resource)]
+pub struct StageHandle(pub Handle<Stage>);
+
+// also the system set each pattern's systems run in
+#[derive(SystemSet, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EnemyPatternKind {
+    Pattern001,
+    Pattern002,
+    Pattern003,
+    Pattern004,
+    Pattern005,
+    Pattern006,
+    Pattern007,
+    Boss,
+}
+
+// one timed spawn on the stage timeline
+#[derive(Deserialize, Clone, Debug)]
+pub struct StageEvent {
+    pub seconds: u64,
+    pub pattern: EnemyPatternKind,
+    pub positions: Vec<(f32, f32)>,
+    pub velocity: (f32, f32),
+    pub point: f32,
+    pub health: f32,
+    pub drop_chance: f64,
+    // min and max seconds between shots
+    pub shoot_interval: (f32, f32),
+    // most each position may move, x and y, rolled per enemy
+    #[serde(default)]
+    pub jitter: (f32, f32),
+    // BulletML script under assets, fires instead of the pattern's own shots
+    #[serde(default)]
+    pub bulletml: Option<String>,
+    #[serde(skip)]
+    pub script: Option<Handle<BulletMl>>,
+}
+
+#[derive(Asset, TypePath, Deserialize, Debug)]
+pub struct Stage {
+    pub events: Vec<StageEvent>,
+}
+
+#[derive(Default)]
+pub struct StageLoader;
+
+#[derive(Debug, Error)]
+pub enum StageLoaderError {
+    #[error("could not read stage file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("could not parse stage file: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for StageLoader {
+    type Asset = Stage;
+    type Settings = ();
+    type Error = StageLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Stage, StageLoaderError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+
+            let mut stage = ron::de::from_bytes::<Stage>(&bytes)?;
+            // the schedule cursor walks the timeline in order
+            stage.events.sort_by_key(|event| event.seconds);
+            // scripts load with the stage so none is missing when its wave comes
+            for event in stage.events.iter_mut() {
+                event.script = event.bulletml.as_ref().map(|path| load_context.load(path));
+            }
+
+            Ok(stage)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["stage.ron"]
+    }
+}
+
+fn stage_load_system(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+) {
+    commands.insert_resource(StageHandle(asset_server.load(STAGE_PATH)));
+}
+
+// game time stands still while loading, so load times never show up in a run
+fn stage_loading_setup_system(
+    mut time: ResMut<Time<Virtual>>,
+) {
+    time.pause();
+}
+
+fn stage_loading_system(
+    stage_handle: Res<StageHandle>,
+    stages: Res<Assets<Stage>>,
+    asset_server: Res<AssetServer>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let scripts_state = asset_server.recursive_dependency_load_state(&stage_handle.0);
+    if stages.contains(&stage_handle.0) && scripts_state == RecursiveDependencyLoadState::Loaded {
+        next_state.set(GameState::Title);
+    } else if asset_server.get_load_state(&stage_handle.0) == Some(LoadState::Failed) {
+        // play on without enemies rather than hang on loading
+        eprintln!("could not load stage: {}", STAGE_PATH);
+        next_state.set(GameState::Title);
+    } else if scripts_state == RecursiveDependencyLoadState::Failed {
+        // enemies with a broken script just hold their fire
+        eprintln!("could not load a bullet script for stage: {}", STAGE_PATH);
+        next_state.set(GameState::Title);
+    }
+}
+
+fn stage_loading_cleanup_system(
+    mut time: ResMut<Time<Virtual>>,
+) {
+    time.unpause();
+}
+
+// a cleared run is offered the high score table the same as a game over
+fn stage_clear_system(
+    mut stage_clear_events: EventReader<StageClearEvent>,
+    player_status: Res<PlayerStatus>,
+    high_scores: Option<Res<HighScores>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if stage_clear_events.read().next().is_some() {
+        next_state.set(run_end_state(high_scores.as_deref(), &player_status));
+    }
+}
@@ -0,0 +1,296 @@
+use bevy::{prelude::*, sprite::{MaterialMesh2dBundle, Mesh2dHandle}, transform::TransformSystem};
+
+use crate::{define::*, enemy::boss::Boss};
+
+// meshes and materials for the game entities, only added when there is a window to render to
+pub struct VisualPlugin;
+
+impl Plugin for VisualPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, (camera_setup_system, game_assets_setup_system))
+            .add_systems(FixedUpdate, (
+                interpolation_restore_system.before(GameSet::Timer),
+                interpolation_record_system.after(GameSet::Item),
+            ))
+            .add_systems(PostUpdate, (
+                player_visual_system,
+                player_focus_visual_system.after(player_visual_system),
+                enemy_visual_system,
+                boss_visual_system.after(enemy_visual_system),
+                hit_flash_visual_system.after(enemy_visual_system),
+                player_shoot_visual_system,
+                enemy_shoot_visual_system,
+                bomb_visual_system,
+                graze_spark_visual_system,
+                score_popup_visual_system,
+                item_visual_system,
+                interpolation_system.before(TransformSystem::TransformPropagate),
+            ));
+    }
+}
+
+// gameplay positions of the last two fixed ticks, the rendered transform is blended between them
+#[derive(Component)]
+struct Interpolated {
+    previous: Vec3,
+    current: Vec3,
+}
+
+// put back the gameplay position before a tick reads it
+fn interpolation_restore_system(
+    mut query: Query<(&mut Transform, &mut Interpolated)>,
+) {
+    for (mut transform, mut interpolated) in query.iter_mut() {
+        transform.translation = interpolated.current;
+        interpolated.previous = interpolated.current;
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn interpolation_record_system(
+    mut commands: Commands,
+    mut query: Query<(Entity, &Transform, Option<&mut Interpolated>, Option<Ref<Velocity>>), Or<(With<Velocity>, With<Player>)>>,
+) {
+    for (entity, transform, interpolated, velocity) in query.iter_mut() {
+        match interpolated {
+            // a pooled bullet handed out again starts over at its new position
+            Some(mut interpolated) if velocity.is_some_and(|velocity| velocity.is_added()) => {
+                interpolated.previous = transform.translation;
+                interpolated.current = transform.translation;
+            }
+            Some(mut interpolated) => interpolated.current = transform.translation,
+            None => {
+                commands.entity(entity).insert(Interpolated {
+                    previous: transform.translation,
+                    current: transform.translation,
+                });
+            }
+        }
+    }
+}
+
+fn interpolation_system(
+    mut query: Query<(&mut Transform, &Interpolated)>,
+    time: Res<Time<Fixed>>,
+) {
+    let alpha = time.overstep_percentage();
+    for (mut transform, interpolated) in query.iter_mut() {
+        transform.translation = interpolated.previous.lerp(interpolated.current, alpha);
+    }
+}
+
+// one mesh and material per kind of entity, shared by every entity of that kind
+#[derive(Resource)]
+pub struct GameAssets {
+    pub player_mesh: Mesh2dHandle,
+    pub player_material: Handle<ColorMaterial>,
+    pub player_hitbox_mesh: Mesh2dHandle,
+    pub enemy_mesh: Mesh2dHandle,
+    pub enemy_material: Handle<ColorMaterial>,
+    pub boss_mesh: Mesh2dHandle,
+    pub item_mesh: Mesh2dHandle,
+    pub item_material: Handle<ColorMaterial>,
+    pub flash_material: Handle<ColorMaterial>,
+    pub graze_spark_mesh: Mesh2dHandle,
+    pub player_shoot_mesh: Mesh2dHandle,
+    pub enemy_shoot_mesh: Mesh2dHandle,
+    pub shoot_material: Handle<ColorMaterial>,
+    // unit circle, scaled by the wave radius
+    pub bomb_mesh: Mesh2dHandle,
+    pub bomb_material: Handle<ColorMaterial>,
+}
+
+fn camera_setup_system(
+    mut commands: Commands,
+) {
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn game_assets_setup_system(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    commands.insert_resource(GameAssets {
+        player_mesh: meshes.add(shape::Circle::new(PLAYER_RADIUS).into()).into(),
+        player_material: materials.add(ColorMaterial::from(Color::BLUE)),
+        player_hitbox_mesh: meshes.add(shape::Circle::new(PLAYER_HITBOX_RADIUS).into()).into(),
+        enemy_mesh: meshes.add(shape::Circle::new(ENEMY_RADIUS).into()).into(),
+        enemy_material: materials.add(ColorMaterial::from(Color::GREEN)),
+        boss_mesh: meshes.add(shape::Circle::new(BOSS_RADIUS).into()).into(),
+        item_mesh: meshes.add(shape::Quad::new(Vec2::splat(ITEM_RADIUS * 2.0)).into()).into(),
+        item_material: materials.add(ColorMaterial::from(Color::YELLOW)),
+        flash_material: materials.add(ColorMaterial::from(Color::WHITE)),
+        graze_spark_mesh: meshes.add(shape::Circle::new(2.0).into()).into(),
+        player_shoot_mesh: meshes.add(shape::Quad::new(Vec2::new(SHOOT_RADIUS * 2.0, SHOOT_RADIUS * 4.0)).into()).into(),
+        enemy_shoot_mesh: meshes.add(shape::Circle::new(SHOOT_RADIUS).into()).into(),
+        shoot_material: materials.add(ColorMaterial::from(Color::RED)),
+        bomb_mesh: meshes.add(shape::Circle::new(1.0).into()).into(),
+        bomb_material: materials.add(ColorMaterial::from(Color::rgba(1.0, 1.0, 1.0, 0.3))),
+    });
+}
+
+fn player_visual_system(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    query: Query<Entity, Added<Player>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).insert((
+            game_assets.player_mesh.clone(),
+            game_assets.player_material.clone(),
+        )).with_children(|p| {
+            p.spawn((
+                MaterialMesh2dBundle {
+                    mesh: game_assets.player_hitbox_mesh.clone(),
+                    material: game_assets.flash_material.clone(),
+                    transform: Transform::from_xyz(0.0, 0.0, 1.0),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                PlayerHitboxDot,
+            ));
+        });
+    }
+}
+
+// the true hitbox only shows while focused
+fn player_focus_visual_system(
+    player: Query<&Player>,
+    mut dots: Query<&mut Visibility, With<PlayerHitboxDot>>,
+) {
+    let is_focus = player.get_single().is_ok_and(|player| player.is_focus);
+    for mut visibility in dots.iter_mut() {
+        *visibility = if is_focus { Visibility::Inherited } else { Visibility::Hidden };
+    }
+}
+
+fn enemy_visual_system(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    query: Query<Entity, Added<Enemy>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).insert((
+            game_assets.enemy_mesh.clone(),
+            game_assets.enemy_material.clone(),
+        ));
+    }
+}
+
+// a bigger body over the one enemy_visual_system gives every enemy
+fn boss_visual_system(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    query: Query<Entity, Added<Boss>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).insert(game_assets.boss_mesh.clone());
+    }
+}
+
+fn hit_flash_visual_system(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    flashed: Query<Entity, Added<HitFlash>>,
+    mut recovered: RemovedComponents<HitFlash>,
+    enemies: Query<(), With<Enemy>>,
+) {
+    for entity in flashed.iter() {
+        commands.entity(entity).insert(game_assets.flash_material.clone());
+    }
+
+    for entity in recovered.read() {
+        if enemies.contains(entity) {
+            commands.entity(entity).insert(game_assets.enemy_material.clone());
+        }
+    }
+}
+
+// pooled bullets come back through here each time they are reused
+fn player_shoot_visual_system(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    query: Query<Entity, Added<FromPlayerShoot>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).insert((
+            game_assets.player_shoot_mesh.clone(),
+            game_assets.shoot_material.clone(),
+        ));
+    }
+}
+
+fn enemy_shoot_visual_system(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    query: Query<Entity, Added<FromEnemyShoot>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).insert((
+            game_assets.enemy_shoot_mesh.clone(),
+            game_assets.shoot_material.clone(),
+        ));
+    }
+}
+
+fn item_visual_system(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    query: Query<Entity, Added<PowerItem>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).insert((
+            game_assets.item_mesh.clone(),
+            game_assets.item_material.clone(),
+        ));
+    }
+}
+
+fn bomb_visual_system(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    query: Query<Entity, Added<BombWave>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).insert((
+            game_assets.bomb_mesh.clone(),
+            game_assets.bomb_material.clone(),
+        ));
+    }
+}
+
+fn graze_spark_visual_system(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    query: Query<Entity, Added<GrazeSpark>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).insert((
+            game_assets.graze_spark_mesh.clone(),
+            game_assets.flash_material.clone(),
+        ));
+    }
+}
+
+fn score_popup_visual_system(
+    mut commands: Commands,
+    query: Query<(Entity, &ScorePopup), Added<ScorePopup>>,
+) {
+    for (entity, score_popup) in query.iter() {
+        commands.entity(entity).with_children(|p| {
+            p.spawn(Text2dBundle {
+                text: Text::from_section(
+                    format!("{}", score_popup.point),
+                    TextStyle {
+                        font_size: 14.0,
+                        color: Color::YELLOW,
+                        ..default()
+                    },
+                ),
+                ..default()
+            });
+        });
+    }
+}